@@ -0,0 +1,149 @@
+//! Compares rendering the compiled templates with the previous implementation
+//! that scanned the template and built a json path selector for every placeholder
+//! on every request.
+
+use std::collections::HashMap;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use serde_json::{json, Value};
+
+#[allow(dead_code)]
+#[path = "../src/server/action/template/mod.rs"]
+mod template;
+
+use template::ActionTemplate;
+
+/// The template implementation before templates were compiled
+mod previous {
+    use jsonpath::Selector;
+    use regex::Regex;
+    use serde_json::Value;
+    use std::collections::HashMap;
+
+    static VALUE_PREFIXES: &[&str] = &["vars.", "params."];
+
+    pub struct ActionTemplate {
+        src: String,
+        matches: Vec<(usize, usize)>,
+    }
+
+    impl ActionTemplate {
+        pub fn new(command: &str) -> Self {
+            let regex = Regex::new(r"\{\{(.*?)\}\}").unwrap();
+            let matches = regex
+                .find_iter(command)
+                .map(|m| (m.start(), m.end()))
+                .collect();
+
+            Self {
+                src: command.to_owned(),
+                matches,
+            }
+        }
+
+        pub fn evaluate(&self, json: &Value, values: &HashMap<String, String>) -> String {
+            let mut result_string = String::with_capacity(self.src.len());
+            let mut last_index = 0;
+
+            for (start, end) in &self.matches {
+                let before = &self.src[last_index..*start];
+                let query = &self.src[*start + 2..*end - 2];
+                result_string.push_str(before);
+                let value = if is_named(query) {
+                    values.get(query).cloned()
+                } else {
+                    evaluate_path(query, json)
+                };
+                result_string.push_str(&value.unwrap_or_default());
+
+                last_index = *end;
+            }
+            result_string.push_str(&self.src[last_index..]);
+
+            result_string
+        }
+    }
+
+    fn is_named(query: &str) -> bool {
+        VALUE_PREFIXES
+            .iter()
+            .any(|prefix| query.starts_with(prefix))
+    }
+
+    fn evaluate_path(query: &str, json: &Value) -> Option<String> {
+        let selector = Selector::new(query).ok()?;
+        let results = selector
+            .find(json)
+            .map(json_to_string)
+            .collect::<Vec<String>>();
+
+        Some(results.join("\n"))
+    }
+
+    fn json_to_string(value: &Value) -> String {
+        match value {
+            Value::Null => String::with_capacity(0),
+            Value::Bool(b) => b.to_string(),
+            Value::Number(n) => n.to_string(),
+            Value::String(s) => s.to_owned(),
+            Value::Array(a) => a
+                .iter()
+                .map(json_to_string)
+                .collect::<Vec<String>>()
+                .join("\n"),
+            Value::Object(o) => o
+                .iter()
+                .map(|(k, v)| format!("{} = {}", k, json_to_string(v)))
+                .collect::<Vec<String>>()
+                .join("\n"),
+        }
+    }
+}
+
+static TEMPLATE: &str = "/srv/{{vars.repo}}/deploy.sh {{$.ref}} {{$.repository.full_name}} \
+    {{$.pusher.name}} {{$.head_commit.id}} {{$.commits.*.id}} {{$.commits.*.message}}";
+
+/// A push event of a monorepo with many commits
+fn push_event() -> Value {
+    let commits = (0..100)
+        .map(|i| {
+            json!({
+                "id": format!("{:040x}", i),
+                "message": format!("Change number {} of the monorepo", i),
+                "author": {"name": "dev", "email": "dev@example.com"},
+                "modified": ["src/main.rs", "README.md"]
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "ref": "refs/heads/main",
+        "repository": {"full_name": "example/monorepo"},
+        "pusher": {"name": "dev"},
+        "head_commit": commits[99].clone(),
+        "commits": commits
+    })
+}
+
+fn render(c: &mut Criterion) {
+    let json = push_event();
+    let values = HashMap::from([(String::from("vars.repo"), String::from("monorepo"))]);
+    let compiled = ActionTemplate::parse(TEMPLATE).unwrap();
+    let previous = previous::ActionTemplate::new(TEMPLATE);
+    assert_eq!(
+        compiled.render(&json, &values, false).unwrap(),
+        previous.evaluate(&json, &values)
+    );
+
+    let mut group = c.benchmark_group("render");
+    group.bench_function("previous", |b| {
+        b.iter(|| previous.evaluate(black_box(&json), black_box(&values)))
+    });
+    group.bench_function("compiled", |b| {
+        b.iter(|| compiled.render(black_box(&json), black_box(&values), false))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, render);
+criterion_main!(benches);
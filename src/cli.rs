@@ -0,0 +1,104 @@
+use clap::{Parser, Subcommand};
+use hyper::header::{HeaderName, HeaderValue};
+use log::LevelFilter;
+use std::path::PathBuf;
+
+use crate::utils::settings::{ListenerSettings, SettingsOverrides};
+
+/// A webhook server
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Args {
+    /// A config file or a directory containing config files.
+    /// Defaults to the multihook directory in the user config directory
+    #[arg(short, long, global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Addresses to listen on. Prefix unix socket paths with `unix:`.
+    /// Overrides the configured listeners
+    #[arg(short, long, global = true)]
+    pub listen: Vec<String>,
+
+    /// The log level (error, warn, info, debug or trace). Overrides RUST_LOG
+    #[arg(long, global = true)]
+    pub log_level: Option<LevelFilter>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Runs the webhook server (default)
+    Serve,
+    /// Validates the config and exits
+    Check,
+    /// Lists all configured endpoints
+    List,
+    /// Prints the JSON schema of the config
+    Schema,
+    /// Executes the action of an endpoint without starting the server
+    Run {
+        /// The name of the endpoint
+        endpoint: String,
+        /// A file containing the request body
+        #[arg(short, long)]
+        body: Option<PathBuf>,
+        /// Request headers in the format `Name:Value`
+        #[arg(short = 'H', long = "header", value_parser = parse_header)]
+        headers: Vec<(HeaderName, HeaderValue)>,
+        /// Path parameters in the format `name=value`
+        #[arg(short, long = "param", value_parser = parse_param)]
+        params: Vec<(String, String)>,
+        /// Validate the configured secret against the headers and body
+        #[arg(long)]
+        verify_secret: bool,
+        /// Print the rendered commands and environment instead of executing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+fn parse_header(header: &str) -> Result<(HeaderName, HeaderValue), String> {
+    let (name, value) = header
+        .split_once(':')
+        .ok_or_else(|| String::from("expected a header in the format `Name:Value`"))?;
+    let name = HeaderName::from_bytes(name.trim().as_bytes()).map_err(|e| e.to_string())?;
+    let value = HeaderValue::from_str(value.trim()).map_err(|e| e.to_string())?;
+
+    Ok((name, value))
+}
+
+fn parse_param(param: &str) -> Result<(String, String), String> {
+    param
+        .split_once('=')
+        .map(|(name, value)| (name.to_owned(), value.to_owned()))
+        .ok_or_else(|| String::from("expected a parameter in the format `name=value`"))
+}
+
+impl Args {
+    /// Returns the settings passed on the command line
+    pub fn overrides(&self) -> SettingsOverrides {
+        SettingsOverrides {
+            listeners: Some(self.listeners()).filter(|listeners| !listeners.is_empty()),
+        }
+    }
+
+    /// Returns the listeners passed on the command line
+    fn listeners(&self) -> Vec<ListenerSettings> {
+        self.listen
+            .iter()
+            .map(|listen| match listen.strip_prefix("unix:") {
+                Some(path) => ListenerSettings::Unix {
+                    path: PathBuf::from(path),
+                    mode: None,
+                    owner: None,
+                    group: None,
+                },
+                None => ListenerSettings::Tcp {
+                    address: listen.to_owned(),
+                },
+            })
+            .collect()
+    }
+}
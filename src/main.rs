@@ -1,11 +1,16 @@
 use std::path::{Path, PathBuf};
+use std::process::exit;
 
+use clap::Parser;
 use utils::logging::init_logger;
-use utils::settings::get_settings;
+use utils::settings::{ConfigLocation, Settings, SettingsOverrides};
+use utils::validation::validate_config;
 
-use crate::server::endpoint::HookEndpoint;
+use crate::cli::{Args, Command};
+use crate::server::endpoint::{HookEndpoint, LocalRequest};
 use crate::server::HookServer;
 
+mod cli;
 mod secret_validation;
 mod server;
 pub(crate) mod utils;
@@ -23,31 +28,114 @@ async fn main() {
 }
 
 async fn init_and_start() {
-    init_logger();
+    let mut args = Args::parse();
+    init_logger(args.log_level);
+    let location = ConfigLocation::from_path(args.config.clone());
+    let command = args.command.take().unwrap_or(Command::Serve);
+    if let Command::Schema = command {
+        return schema();
+    }
+    let mut settings = match validate_config(&location) {
+        Ok(settings) => settings,
+        Err(errors) => {
+            for error in &errors {
+                log::error!("{}", error);
+            }
+            log::error!("Invalid config ({} errors)", errors.len());
+            exit(1);
+        }
+    };
+    let overrides = args.overrides();
+    overrides.apply(&mut settings);
+
+    match command {
+        Command::Serve => serve(settings, location, overrides).await,
+        Command::Check => check(&settings),
+        Command::List => list(&settings),
+        Command::Schema => schema(),
+        Command::Run {
+            endpoint,
+            body,
+            headers,
+            params,
+            verify_secret,
+            dry_run,
+        } => {
+            let request = LocalRequest {
+                headers: headers.into_iter().collect(),
+                params: params.into_iter().collect(),
+                verify_secret,
+                dry_run,
+                ..Default::default()
+            };
+            run(&settings, &endpoint, body, request).await
+        }
+    }
+}
+
+/// Prints the JSON schema generated from the settings
+fn schema() {
+    let schema = schemars::schema_for!(Settings);
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&schema).expect("Failed to serialize schema")
+    );
+}
+
+async fn serve(settings: Settings, location: ConfigLocation, overrides: SettingsOverrides) {
     let data_dir = dirs::data_dir()
         .map(|d| d.join("multihook"))
         .unwrap_or(PathBuf::from("."));
     if !Path::new(&data_dir).exists() {
         std::fs::create_dir(data_dir).expect("Failed to create data dir");
     }
-    let settings = get_settings();
-    let mut server = HookServer::new();
-
-    for (name, endpoint) in &settings.endpoints {
-        log::info!("Adding endpoint '{}' with path '{}'", name, &endpoint.path);
-        server.add_hook(
-            endpoint.path.clone(),
-            HookEndpoint::from_config(name, &settings, &endpoint),
-        )
-    }
+    let server = HookServer::new(&settings, location, overrides).expect("Failed to create server");
 
-    let address = settings
-        .server
-        .address
-        .clone()
-        .unwrap_or(String::from("127.0.0.1:8080"));
     server
-        .start(&address)
+        .start(&settings)
         .await
         .expect("Failed to start server");
 }
+
+fn check(settings: &Settings) {
+    println!("Config is valid ({} endpoints)", settings.endpoints.len());
+}
+
+fn list(settings: &Settings) {
+    let mut endpoints = settings.endpoints.iter().collect::<Vec<_>>();
+    endpoints.sort_by_key(|(name, _)| *name);
+
+    for (name, endpoint) in endpoints {
+        println!("{}\t/{}\t{}", name, endpoint.path, endpoint.action);
+    }
+}
+
+async fn run(settings: &Settings, name: &str, body: Option<PathBuf>, mut request: LocalRequest) {
+    let Some(endpoint) = settings.endpoints.get(name) else {
+        log::error!("Endpoint '{}' does not exist", name);
+        exit(1);
+    };
+    request.body = match body.map(std::fs::read).transpose() {
+        Ok(body) => body.unwrap_or_default(),
+        Err(e) => {
+            log::error!("Failed to read body: {}", e);
+            exit(1);
+        }
+    };
+    let dry_run = request.dry_run;
+    let endpoint = match HookEndpoint::from_config(name, settings, endpoint) {
+        Ok(endpoint) => endpoint,
+        Err(e) => {
+            log::error!("Invalid endpoint '{}': {}", name, e);
+            exit(1);
+        }
+    };
+
+    if let Err(e) = endpoint.execute_local(request).await {
+        log::error!("Endpoint '{}' failed: {}", name, e);
+        exit(1);
+    }
+    if !dry_run {
+        log::info!("Endpoint '{}' executed", name);
+    }
+}
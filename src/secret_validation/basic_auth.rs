@@ -0,0 +1,32 @@
+use crate::secret_validation::{authorization_credentials, credentials_match, SecretValidator};
+use crate::utils::error::{MultihookError, MultihookResult};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hyper::header::AUTHORIZATION;
+use hyper::http::request::Parts;
+
+pub struct BasicAuthValidator;
+
+impl SecretValidator for BasicAuthValidator {
+    fn validate(&self, parts: &Parts, _body: &[u8], secret: &[u8]) -> MultihookResult<()> {
+        log::debug!("Validating Basic Auth credentials");
+        let Some(header) = parts.headers.get(AUTHORIZATION) else {
+            log::error!("Missing Authorization Header");
+            return Err(MultihookError::MissingSecret);
+        };
+        let Some(encoded) = authorization_credentials(header, "Basic") else {
+            log::error!("Authorization Header doesn't contain Basic credentials");
+            return Err(MultihookError::MissingSecret);
+        };
+        let Ok(credentials) = STANDARD.decode(encoded) else {
+            log::error!("Received credentials cannot be decoded from base64");
+            return Err(MultihookError::InvalidSecret);
+        };
+
+        if credentials_match(&credentials, secret) {
+            Ok(())
+        } else {
+            Err(MultihookError::InvalidSecret)
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use crate::secret_validation::SecretValidator;
+use crate::utils::error::{MultihookError, MultihookResult};
 use hmac::{Hmac, Mac};
-use hyper::HeaderMap;
+use hyper::http::request::Parts;
 use sha2::Sha256;
 
 pub struct HMacSecretValidator;
@@ -13,31 +14,32 @@ static SUM_HEADERS: &[&str] = &[
 ];
 
 impl SecretValidator for HMacSecretValidator {
-    fn validate(&self, headers: &HeaderMap, body: &[u8], secret: &[u8]) -> bool {
+    fn validate(&self, parts: &Parts, body: &[u8], secret: &[u8]) -> MultihookResult<()> {
         log::debug!("Validating HMac Secret");
-        let header = headers
+        let header = parts
+            .headers
             .iter()
-            .filter(|(name, _)| SUM_HEADERS.iter().find(|h| **name == **h).is_some())
-            .next();
+            .find(|(name, _)| SUM_HEADERS.iter().any(|h| **name == **h));
 
         if let Some((_, sum)) = header {
             let mut mac = Hmac::<Sha256>::new_from_slice(secret).unwrap();
             mac.update(body);
             let Ok(sum) = sum.to_str() else {
                 log::error!("Received signature is not a valid string");
-                return false;
+                return Err(MultihookError::InvalidSecret);
             };
 
             let Ok(decoded_secret) = hex::decode(sum.trim_start_matches("sha256=")) else {
                 log::error!("Received signature cannot be decoded from hex");
-                return false;
+                return Err(MultihookError::InvalidSecret);
             };
             log::debug!("Verifying found signature");
 
-            mac.verify_slice(&decoded_secret).is_ok()
+            mac.verify_slice(&decoded_secret)
+                .map_err(|_| MultihookError::InvalidSecret)
         } else {
             log::error!("Missing Signature Header");
-            false
+            Err(MultihookError::MissingSecret)
         }
     }
 }
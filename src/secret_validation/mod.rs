@@ -1,22 +1,70 @@
+mod basic_auth;
 mod hash_mac;
+mod token;
 
+use crate::secret_validation::basic_auth::BasicAuthValidator;
 use crate::secret_validation::hash_mac::HMacSecretValidator;
-use hyper::HeaderMap;
+use crate::secret_validation::token::{
+    BearerTokenValidator, QueryTokenValidator, QUERY_TOKEN_PARAM,
+};
+use crate::utils::error::MultihookResult;
+use hyper::header::HeaderValue;
+use hyper::http::request::Parts;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
 pub enum SecretFormat {
     HMac,
+    /// HTTP Basic authentication with the secret in the format `user:password`
+    Basic,
+    /// `Authorization: Bearer <secret>` header
+    Bearer,
+    /// `token=<secret>` query parameter
+    QueryToken,
 }
 
 impl SecretFormat {
-    pub fn validator(&self) -> impl SecretValidator {
+    pub fn validator(&self) -> Box<dyn SecretValidator + Send + Sync> {
         match self {
-            SecretFormat::HMac => HMacSecretValidator,
+            SecretFormat::HMac => Box::new(HMacSecretValidator),
+            SecretFormat::Basic => Box::new(BasicAuthValidator),
+            SecretFormat::Bearer => Box::new(BearerTokenValidator),
+            SecretFormat::QueryToken => Box::new(QueryTokenValidator),
+        }
+    }
+
+    /// Returns the query parameter that carries the secret
+    pub fn query_param(&self) -> Option<&'static str> {
+        match self {
+            SecretFormat::QueryToken => Some(QUERY_TOKEN_PARAM),
+            _ => None,
         }
     }
 }
 
 pub trait SecretValidator {
-    fn validate(&self, headers: &HeaderMap, body: &[u8], secret: &[u8]) -> bool;
+    /// Validates the request against the secret. Returns [MultihookError::MissingSecret]
+    /// if the request doesn't contain any credentials and [MultihookError::InvalidSecret]
+    /// if the credentials don't match.
+    ///
+    /// [MultihookError::MissingSecret]: crate::utils::error::MultihookError::MissingSecret
+    /// [MultihookError::InvalidSecret]: crate::utils::error::MultihookError::InvalidSecret
+    fn validate(&self, parts: &Parts, body: &[u8], secret: &[u8]) -> MultihookResult<()>;
+}
+
+/// Compares the received credentials with the secret in constant time
+fn credentials_match(received: &[u8], secret: &[u8]) -> bool {
+    received.ct_eq(secret).into()
+}
+
+/// Returns the credentials of an `Authorization` header if it uses the given scheme.
+/// The scheme is compared case-insensitively
+fn authorization_credentials<'a>(header: &'a HeaderValue, scheme: &str) -> Option<&'a str> {
+    let (received_scheme, credentials) = header.to_str().ok()?.trim_start().split_once(' ')?;
+
+    received_scheme
+        .eq_ignore_ascii_case(scheme)
+        .then(|| credentials.trim())
 }
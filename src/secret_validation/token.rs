@@ -0,0 +1,51 @@
+use crate::secret_validation::{authorization_credentials, credentials_match, SecretValidator};
+use crate::utils::error::{MultihookError, MultihookResult};
+use hyper::header::AUTHORIZATION;
+use hyper::http::request::Parts;
+
+pub static QUERY_TOKEN_PARAM: &str = "token";
+
+pub struct BearerTokenValidator;
+
+impl SecretValidator for BearerTokenValidator {
+    fn validate(&self, parts: &Parts, _body: &[u8], secret: &[u8]) -> MultihookResult<()> {
+        log::debug!("Validating Bearer Token");
+        let Some(header) = parts.headers.get(AUTHORIZATION) else {
+            log::error!("Missing Authorization Header");
+            return Err(MultihookError::MissingSecret);
+        };
+        let Some(token) = authorization_credentials(header, "Bearer") else {
+            log::error!("Authorization Header doesn't contain a Bearer Token");
+            return Err(MultihookError::MissingSecret);
+        };
+
+        if credentials_match(token.as_bytes(), secret) {
+            Ok(())
+        } else {
+            Err(MultihookError::InvalidSecret)
+        }
+    }
+}
+
+pub struct QueryTokenValidator;
+
+impl SecretValidator for QueryTokenValidator {
+    fn validate(&self, parts: &Parts, _body: &[u8], secret: &[u8]) -> MultihookResult<()> {
+        log::debug!("Validating Query Token");
+        let token = parts.uri.query().and_then(|query| {
+            form_urlencoded::parse(query.as_bytes())
+                .find(|(key, _)| key == QUERY_TOKEN_PARAM)
+                .map(|(_, value)| value)
+        });
+        let Some(token) = token else {
+            log::error!("Missing Token Query Parameter");
+            return Err(MultihookError::MissingSecret);
+        };
+
+        if credentials_match(token.as_bytes(), secret) {
+            Ok(())
+        } else {
+            Err(MultihookError::InvalidSecret)
+        }
+    }
+}
@@ -1,6 +1,9 @@
 use crate::utils::error::{MultihookError, MultihookResult};
 
-use self::template::ActionTemplate;
+use self::template::{ActionTemplate, RenderError};
+use crate::server::jobs;
+use crate::utils::systemd;
+use std::process::Stdio;
 use std::{collections::HashMap, sync::Arc};
 use tokio::{process::Command, sync::Semaphore};
 
@@ -11,43 +14,143 @@ static MAX_CONCURRENCY: usize = 256;
 #[derive(Clone)]
 pub struct Action {
     template: ActionTemplate,
+    variables: HashMap<String, String>,
     semaphore: Arc<Semaphore>,
 }
 
 impl Action {
-    /// Creates a new command that also checks for parallel runs
-    pub fn new<S: Into<String>>(command: S, allow_parallel: bool) -> Self {
+    /// Creates a new command that also checks for parallel runs.
+    /// Fails if the template of the command is invalid
+    pub fn new<S: AsRef<str>>(command: S, allow_parallel: bool) -> MultihookResult<Self> {
         let semaphore = if allow_parallel {
             Semaphore::new(MAX_CONCURRENCY)
         } else {
             Semaphore::new(1)
         };
 
-        Self {
-            template: ActionTemplate::new(command.into()),
+        Ok(Self {
+            template: ActionTemplate::parse(command.as_ref())
+                .map_err(MultihookError::TemplateError)?,
+            variables: HashMap::new(),
             semaphore: Arc::new(semaphore),
+        })
+    }
+
+    /// Sets the variables that can be used in the command with `{{vars.name}}`
+    pub fn variables(mut self, variables: HashMap<String, String>) -> Self {
+        self.variables = variables
+            .into_iter()
+            .map(|(name, value)| (format!("vars.{}", name), value))
+            .collect();
+
+        self
+    }
+
+    /// Validates the syntax and the placeholders of the command.
+    /// Named values like `vars.name` are only checked if the defined names are given.
+    /// Returns a description of each error
+    pub fn validate(command: &str, names: Option<&[String]>) -> Vec<String> {
+        match ActionTemplate::parse(command) {
+            Ok(template) => names
+                .map(|names| template.invalid_queries(names))
+                .unwrap_or_default()
+                .into_iter()
+                .map(|query| format!("invalid placeholder '{{{{{}}}}}'", query))
+                .collect(),
+            Err(e) => vec![format!("invalid template: {}", e)],
+        }
+    }
+
+    /// Renders the command with the values of the body, the variables
+    /// and the path parameters
+    pub fn render(
+        &self,
+        body: &serde_json::Value,
+        params: &HashMap<String, String>,
+    ) -> MultihookResult<String> {
+        self.render_template(body, params, false)
+    }
+
+    /// Fails if any placeholder of the command rejects its value or,
+    /// in strict mode, resolves to an empty value
+    pub fn check_placeholders(
+        &self,
+        body: &serde_json::Value,
+        params: &HashMap<String, String>,
+        strict: bool,
+    ) -> MultihookResult<()> {
+        if !strict && !self.template.is_fallible() {
+            return Ok(());
         }
+
+        self.render_template(body, params, strict).map(|_| ())
+    }
+
+    fn render_template(
+        &self,
+        body: &serde_json::Value,
+        params: &HashMap<String, String>,
+        strict: bool,
+    ) -> MultihookResult<String> {
+        self.template
+            .render(body, &self.values(params), strict)
+            .map_err(|e| match e {
+                RenderError::Empty(placeholder) => MultihookError::EmptyPlaceholder(placeholder),
+                RenderError::InvalidValue(placeholder, reason) => {
+                    MultihookError::InvalidPlaceholder(placeholder, reason)
+                }
+            })
+    }
+
+    /// Returns the named values of the variables and the path parameters
+    fn values(&self, params: &HashMap<String, String>) -> HashMap<String, String> {
+        let mut values = self.variables.clone();
+        values.extend(
+            params
+                .iter()
+                .map(|(name, value)| (format!("params.{}", name), value.to_owned())),
+        );
+
+        values
     }
 
     /// Executes the action
     pub async fn run(
         &self,
         body: &serde_json::Value,
-        env: &HashMap<&str, String>,
+        params: &HashMap<String, String>,
+        env: &HashMap<String, String>,
     ) -> MultihookResult<()> {
-        let command = self.template.evaluate(&body);
+        let command_string = self.render(body, params)?;
         log::debug!("Acquiring lock for parallel runs...");
         let permit = self.semaphore.acquire().await.unwrap();
         log::debug!("Lock acquired. Running command...");
         std::mem::drop(permit);
 
-        let output = Command::new("sh")
+        let mut command = std::process::Command::new("sh");
+        for var in systemd::LISTEN_VARS {
+            command.env_remove(var);
+        }
+        command
             .envs(env)
             .arg("-c")
-            .arg(command)
-            .kill_on_drop(true)
-            .output()
-            .await?;
+            .arg(command_string)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        // runs the action in its own process group so that all of its
+        // processes can be terminated on shutdown or when it is cancelled
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+
+        let child = Command::from(command).kill_on_drop(true).spawn()?;
+        let process = child
+            .id()
+            .map(|pid| jobs::track_process(pid, env.get("HOOK_NAME").cloned().unwrap_or_default()));
+        let output = child.wait_with_output().await?;
+        if let Some(process) = process {
+            process.exited();
+        }
         log::debug!("Command finished. Releasing parallel lock...");
 
         let stderr = String::from_utf8_lossy(&output.stderr[..]);
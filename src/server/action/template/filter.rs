@@ -0,0 +1,187 @@
+use serde_json::Value;
+
+use super::{is_empty, json_to_string};
+
+/// A filter that transforms the value of a placeholder
+#[derive(Clone, Debug)]
+pub enum Filter {
+    /// Replaces empty values with the given one
+    Default(Value),
+    TrimPrefix(String),
+    TrimSuffix(String),
+    Trim,
+    Lower,
+    Upper,
+    Replace(String, String),
+    /// Serializes the value to json
+    Json,
+    /// Quotes the value for the use as a single shell argument
+    ShellQuote,
+    First,
+    Last,
+    Join(String),
+    /// Quotes each element of an array as a separate shell argument
+    ShellArray,
+    /// Writes each element of an array on a separate line. Nested values are written as json
+    Lines,
+    /// Writes an array as a csv row and an array of arrays as csv rows
+    Csv,
+    /// Fails if the value is an array or an object
+    Scalar,
+}
+
+impl Filter {
+    /// Creates the filter with the given name and arguments
+    pub fn new(name: &str, args: Vec<Value>) -> Result<Self, String> {
+        let filter = match (name, args.as_slice()) {
+            ("default", [value]) => Filter::Default(value.clone()),
+            ("trim_prefix", [prefix]) => Filter::TrimPrefix(json_to_string(prefix)),
+            ("trim_suffix", [suffix]) => Filter::TrimSuffix(json_to_string(suffix)),
+            ("trim", []) => Filter::Trim,
+            ("lower", []) => Filter::Lower,
+            ("upper", []) => Filter::Upper,
+            ("replace", [from, to]) => Filter::Replace(json_to_string(from), json_to_string(to)),
+            ("json", []) => Filter::Json,
+            ("shell_quote", []) => Filter::ShellQuote,
+            ("first", []) => Filter::First,
+            ("last", []) => Filter::Last,
+            ("join", [separator]) => Filter::Join(json_to_string(separator)),
+            ("shell_array", []) => Filter::ShellArray,
+            ("lines", []) => Filter::Lines,
+            ("csv", []) => Filter::Csv,
+            ("scalar", []) => Filter::Scalar,
+            (
+                "default" | "trim_prefix" | "trim_suffix" | "trim" | "lower" | "upper" | "replace"
+                | "json" | "shell_quote" | "first" | "last" | "join" | "shell_array" | "lines"
+                | "csv" | "scalar",
+                _,
+            ) => return Err(format!("wrong number of arguments for filter '{}'", name)),
+            _ => return Err(format!("unknown filter '{}'", name)),
+        };
+
+        Ok(filter)
+    }
+
+    /// Returns true if the filter can reject values
+    pub fn is_fallible(&self) -> bool {
+        matches!(self, Filter::Scalar)
+    }
+
+    /// Applies the filter to the value. Fails with the reason if the value can't be used
+    pub fn apply(&self, value: Value) -> Result<Value, String> {
+        let value = match self {
+            Filter::Default(default) if is_empty(&value) => default.clone(),
+            Filter::Default(_) => value,
+            Filter::TrimPrefix(prefix) => map_string(value, |s| {
+                s.strip_prefix(prefix.as_str()).unwrap_or(s).to_owned()
+            }),
+            Filter::TrimSuffix(suffix) => map_string(value, |s| {
+                s.strip_suffix(suffix.as_str()).unwrap_or(s).to_owned()
+            }),
+            Filter::Trim => map_string(value, |s| s.trim().to_owned()),
+            Filter::Lower => map_string(value, str::to_lowercase),
+            Filter::Upper => map_string(value, str::to_uppercase),
+            Filter::Replace(from, to) => map_string(value, |s| s.replace(from.as_str(), to)),
+            Filter::Json => Value::String(value.to_string()),
+            Filter::ShellQuote => map_string(value, shell_quote),
+            Filter::First => match value {
+                Value::Array(values) => values.into_iter().next().unwrap_or_default(),
+                value => value,
+            },
+            Filter::Last => match value {
+                Value::Array(values) => values.into_iter().last().unwrap_or_default(),
+                value => value,
+            },
+            Filter::Join(separator) => match value {
+                Value::Array(values) => Value::String(
+                    values
+                        .iter()
+                        .map(json_to_string)
+                        .collect::<Vec<_>>()
+                        .join(separator),
+                ),
+                value => value,
+            },
+            Filter::ShellArray => Value::String(
+                elements(value)
+                    .iter()
+                    .map(|value| shell_quote(&compact_string(value)))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+            Filter::Lines => Value::String(
+                elements(value)
+                    .iter()
+                    .map(compact_string)
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+            Filter::Csv => {
+                let values = elements(value);
+                let rows = if values.iter().all(Value::is_array) {
+                    values.into_iter().map(elements).collect()
+                } else {
+                    vec![values]
+                };
+                Value::String(
+                    rows.iter()
+                        .map(|row| csv_row(row))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                )
+            }
+            Filter::Scalar => match value {
+                Value::Array(_) | Value::Object(_) => {
+                    return Err(String::from("is not a scalar value"))
+                }
+                value => value,
+            },
+        };
+
+        Ok(value)
+    }
+}
+
+/// Returns the elements of an array or the value itself if it isn't an array.
+/// Null results in no elements
+fn elements(value: Value) -> Vec<Value> {
+    match value {
+        Value::Null => Vec::new(),
+        Value::Array(values) => values,
+        value => vec![value],
+    }
+}
+
+/// Returns the string representation of scalars and compact json for arrays and objects
+fn compact_string(value: &Value) -> String {
+    match value {
+        Value::Array(_) | Value::Object(_) => value.to_string(),
+        value => json_to_string(value),
+    }
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Formats the values as a csv row. Fields containing separators, quotes
+/// or line breaks are quoted
+fn csv_row(values: &[Value]) -> String {
+    values
+        .iter()
+        .map(|value| {
+            let field = compact_string(value);
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Applies the function to the string representation of the value
+fn map_string<F: Fn(&str) -> String>(value: Value, f: F) -> Value {
+    Value::String(f(&json_to_string(&value)))
+}
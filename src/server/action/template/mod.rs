@@ -0,0 +1,434 @@
+use serde_json::Value;
+use serde_json_path::JsonPath;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use self::filter::Filter;
+
+mod filter;
+mod parser;
+
+/// Placeholders starting with one of these prefixes are replaced with named values
+/// like variables and path parameters instead of being evaluated as json paths
+static VALUE_PREFIXES: &[&str] = &["vars.", "params."];
+
+#[derive(Clone, Debug)]
+pub struct ActionTemplate {
+    nodes: Vec<Node>,
+    /// True if any filter can fail at runtime
+    fallible: bool,
+}
+
+/// An error of a placeholder that is returned when rendering the template
+#[derive(Clone, Debug)]
+pub enum RenderError {
+    /// The placeholder resolved to an empty value in strict mode
+    Empty(String),
+    /// A filter rejected the value of the placeholder
+    InvalidValue(String, String),
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    Text(String),
+    Placeholder(Expression),
+    If {
+        condition: Condition,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+}
+
+#[derive(Clone, Debug)]
+struct Expression {
+    /// The source of the expression for error messages
+    text: String,
+    source: Source,
+    filters: Vec<Filter>,
+}
+
+#[derive(Clone, Debug)]
+enum Source {
+    Path(JsonPath),
+    Named(String),
+}
+
+#[derive(Clone, Debug)]
+struct Condition {
+    negated: bool,
+    expression: Expression,
+    comparison: Option<Comparison>,
+}
+
+#[derive(Clone, Debug)]
+enum Comparison {
+    Equal(Value),
+    NotEqual(Value),
+}
+
+impl ActionTemplate {
+    /// Parses the template. Returns a description of the error if the syntax is invalid
+    pub fn parse(command: &str) -> Result<Self, String> {
+        let nodes = parser::parse(command)?;
+        let mut expressions = Vec::new();
+        collect_expressions(&nodes, &mut expressions);
+        let fallible = expressions
+            .iter()
+            .flat_map(|expression| &expression.filters)
+            .any(Filter::is_fallible);
+
+        Ok(Self { nodes, fallible })
+    }
+
+    /// Returns true if rendering can fail with a value rejected by a filter
+    pub fn is_fallible(&self) -> bool {
+        self.fallible
+    }
+
+    /// Returns the queries of all placeholders that reference values that aren't defined.
+    /// Json paths are already validated when parsing the template
+    pub fn invalid_queries(&self, names: &[String]) -> Vec<&str> {
+        let mut expressions = Vec::new();
+        collect_expressions(&self.nodes, &mut expressions);
+
+        expressions
+            .into_iter()
+            .filter(|expression| match &expression.source {
+                Source::Path(_) => false,
+                Source::Named(name) => !names.iter().any(|n| n == name),
+            })
+            .map(|expression| expression.text.as_str())
+            .collect()
+    }
+
+    /// Replaces the placeholders with the values of the json body and the named values.
+    /// In strict mode rendering fails with the placeholder that resolves to an empty value
+    pub fn render(
+        &self,
+        json: &Value,
+        values: &HashMap<String, String>,
+        strict: bool,
+    ) -> Result<String, RenderError> {
+        let mut output = String::with_capacity(self.len_hint());
+        render_nodes(&self.nodes, json, values, strict, &mut output)?;
+
+        Ok(output)
+    }
+
+    /// Returns the length of the static text of the template
+    fn len_hint(&self) -> usize {
+        self.nodes
+            .iter()
+            .map(|node| match node {
+                Node::Text(text) => text.len(),
+                _ => 0,
+            })
+            .sum()
+    }
+}
+
+fn collect_expressions<'a>(nodes: &'a [Node], expressions: &mut Vec<&'a Expression>) {
+    for node in nodes {
+        match node {
+            Node::Text(_) => {}
+            Node::Placeholder(expression) => expressions.push(expression),
+            Node::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                expressions.push(&condition.expression);
+                collect_expressions(then, expressions);
+                collect_expressions(otherwise, expressions);
+            }
+        }
+    }
+}
+
+fn render_nodes(
+    nodes: &[Node],
+    json: &Value,
+    values: &HashMap<String, String>,
+    strict: bool,
+    output: &mut String,
+) -> Result<(), RenderError> {
+    for node in nodes {
+        match node {
+            Node::Text(text) => output.push_str(text),
+            Node::Placeholder(expression) => {
+                let start = output.len();
+                let value = expression.evaluate(json, values)?;
+                write_value(&value, output);
+                if strict && output.len() == start {
+                    return Err(RenderError::Empty(expression.text.clone()));
+                }
+            }
+            Node::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let branch = if condition.evaluate(json, values)? {
+                    then
+                } else {
+                    otherwise
+                };
+                render_nodes(branch, json, values, strict, output)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+impl Expression {
+    /// Evaluates the expression. The value is only copied if filters need to modify it
+    fn evaluate<'a>(
+        &self,
+        json: &'a Value,
+        values: &HashMap<String, String>,
+    ) -> Result<Cow<'a, Value>, RenderError> {
+        let value = match &self.source {
+            Source::Path(path) => evaluate_path(path, json),
+            Source::Named(name) => Cow::Owned(
+                values
+                    .get(name)
+                    .cloned()
+                    .map(Value::String)
+                    .unwrap_or_default(),
+            ),
+        };
+        if self.filters.is_empty() {
+            return Ok(value);
+        }
+
+        self.filters
+            .iter()
+            .try_fold(value.into_owned(), |value, filter| filter.apply(value))
+            .map(Cow::Owned)
+            .map_err(|e| RenderError::InvalidValue(self.text.clone(), e))
+    }
+}
+
+impl Condition {
+    fn evaluate(
+        &self,
+        json: &Value,
+        values: &HashMap<String, String>,
+    ) -> Result<bool, RenderError> {
+        let value = self.expression.evaluate(json, values)?;
+        let result = match &self.comparison {
+            None => !is_empty(&value),
+            Some(Comparison::Equal(other)) => json_to_string(&value) == json_to_string(other),
+            Some(Comparison::NotEqual(other)) => json_to_string(&value) != json_to_string(other),
+        };
+
+        Ok(result != self.negated)
+    }
+}
+
+/// Returns the single match of the path, an array of all matches
+/// if there are multiple or null if there are none
+fn evaluate_path<'a>(path: &JsonPath, json: &'a Value) -> Cow<'a, Value> {
+    let mut results = path.query(json).all();
+
+    match results.len() {
+        0 => Cow::Owned(Value::Null),
+        1 => Cow::Borrowed(results.remove(0)),
+        _ => Cow::Owned(Value::Array(results.into_iter().cloned().collect())),
+    }
+}
+
+/// Returns true for null, false, empty strings, arrays and objects
+fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::Bool(b) => !b,
+        Value::Number(_) => false,
+        Value::String(s) => s.is_empty(),
+        Value::Array(a) => a.is_empty(),
+        Value::Object(o) => o.is_empty(),
+    }
+}
+
+fn json_to_string(value: &Value) -> String {
+    let mut output = String::new();
+    write_value(value, &mut output);
+
+    output
+}
+
+/// Writes the string representation of the value to the output.
+/// Array elements and object fields are written on separate lines
+fn write_value(value: &Value, output: &mut String) {
+    match value {
+        Value::Null => {}
+        Value::Bool(b) => write!(output, "{}", b).unwrap(),
+        Value::Number(n) => write!(output, "{}", n).unwrap(),
+        Value::String(s) => output.push_str(s),
+        Value::Array(a) => {
+            for (i, value) in a.iter().enumerate() {
+                if i > 0 {
+                    output.push('\n');
+                }
+                write_value(value, output);
+            }
+        }
+        Value::Object(o) => {
+            for (i, (key, value)) in o.iter().enumerate() {
+                if i > 0 {
+                    output.push('\n');
+                }
+                write!(output, "{} = ", key).unwrap();
+                write_value(value, output);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// The example document of RFC 9535 section 1.5
+    fn store() -> Value {
+        json!({
+            "store": {
+                "book": [
+                    {"category": "reference", "author": "Nigel Rees",
+                     "title": "Sayings of the Century", "price": 8.95},
+                    {"category": "fiction", "author": "Evelyn Waugh",
+                     "title": "Sword of Honour", "price": 12.99},
+                    {"category": "fiction", "author": "Herman Melville",
+                     "title": "Moby Dick", "isbn": "0-553-21311-3", "price": 8.99},
+                    {"category": "fiction", "author": "J. R. R. Tolkien",
+                     "title": "The Lord of the Rings", "isbn": "0-395-19395-8", "price": 22.99}
+                ],
+                "bicycle": {"color": "red", "price": 399}
+            }
+        })
+    }
+
+    fn render(template: &str, json: &Value) -> String {
+        ActionTemplate::parse(template)
+            .unwrap()
+            .render(json, &HashMap::new(), false)
+            .unwrap()
+    }
+
+    #[test]
+    fn it_evaluates_the_rfc_examples() {
+        let store = store();
+        let authors = r#"["Nigel Rees","Evelyn Waugh","Herman Melville","J. R. R. Tolkien"]"#;
+
+        assert_eq!(render("{{$.store.book[*].author | json}}", &store), authors);
+        assert_eq!(render("{{$..author | json}}", &store), authors);
+        assert_eq!(
+            render("{{$.store..price | lines}}", &store).lines().count(),
+            5
+        );
+        assert_eq!(render("{{$..book[2].author}}", &store), "Herman Melville");
+        assert_eq!(render("{{$..book[2].publisher}}", &store), "");
+        assert_eq!(
+            render("{{$..book[-1].title}}", &store),
+            "The Lord of the Rings"
+        );
+        assert_eq!(
+            render("{{$..book[0,1].title | join(\",\")}}", &store),
+            "Sayings of the Century,Sword of Honour"
+        );
+        assert_eq!(
+            render("{{$..book[:2].title | join(\",\")}}", &store),
+            "Sayings of the Century,Sword of Honour"
+        );
+        assert_eq!(
+            render("{{$..book[?@.isbn].title | join(\",\")}}", &store),
+            "Moby Dick,The Lord of the Rings"
+        );
+        assert_eq!(
+            render("{{$..book[?@.price<10].title | join(\",\")}}", &store),
+            "Sayings of the Century,Moby Dick"
+        );
+    }
+
+    #[test]
+    fn it_evaluates_filter_selectors() {
+        let json = json!({"commits": [
+            {"id": 1, "author": {"name": "x"}},
+            {"id": 2, "author": {"name": "y"}},
+            {"id": 3, "author": {"name": "x"}}
+        ]});
+
+        assert_eq!(
+            render("{{$.commits[?@.author.name=='x'].id | join(\",\")}}", &json),
+            "1,3"
+        );
+        assert_eq!(
+            render("{{$.commits[?@.author.name!='x' && @.id>1].id}}", &json),
+            "2"
+        );
+        assert_eq!(render("{{$.commits[?@.id>5].id}}", &json), "");
+    }
+
+    #[test]
+    fn it_evaluates_slices() {
+        // RFC 9535 section 2.3.4.3
+        let json = json!(["a", "b", "c", "d", "e", "f", "g"]);
+
+        assert_eq!(render("{{$[1:3] | join(\",\")}}", &json), "b,c");
+        assert_eq!(render("{{$[5:] | join(\",\")}}", &json), "f,g");
+        assert_eq!(render("{{$[1:5:2] | join(\",\")}}", &json), "b,d");
+        assert_eq!(render("{{$[5:1:-2] | join(\",\")}}", &json), "f,d");
+        assert_eq!(render("{{$[::-1] | join(\",\")}}", &json), "g,f,e,d,c,b,a");
+    }
+
+    #[test]
+    fn it_evaluates_name_and_index_selectors() {
+        // RFC 9535 sections 2.3.1.3 and 2.3.3.3
+        let json = json!({"o": {"j j": {"k.k": 3}}, "'": {"@": 2}, "a": ["a", "b"]});
+
+        assert_eq!(render("{{$.o['j j']['k.k']}}", &json), "3");
+        assert_eq!(render("{{$.o[\"j j\"][\"k.k\"]}}", &json), "3");
+        assert_eq!(render("{{$[\"'\"][\"@\"]}}", &json), "2");
+        assert_eq!(render("{{$.a[1]}}", &json), "b");
+        assert_eq!(render("{{$.a[-2]}}", &json), "a");
+        assert_eq!(render("{{$.a[2]}}", &json), "");
+    }
+
+    #[test]
+    fn it_keeps_raw_blocks() {
+        let json = json!({"name": "web"});
+
+        assert_eq!(
+            render(
+                "docker inspect -f '{% raw %}{{.State.Status}}{% endraw %}' {{$.name}}",
+                &json
+            ),
+            "docker inspect -f '{{.State.Status}}' web"
+        );
+        assert_eq!(
+            render("printf '{%raw%}{%s}{% if %}{%endraw%}'", &json),
+            "printf '{%s}{% if %}'"
+        );
+        assert!(ActionTemplate::parse("{% raw %}{{.Name}}").is_err());
+    }
+
+    #[test]
+    fn it_rejects_invalid_paths_when_parsing() {
+        for template in [
+            "{{$.a[?@.b==]}}",
+            "{{$[}}",
+            "{{$.a[1:2:x]}}",
+            "{{$.a[01]}}",
+            "{{$..}}",
+        ] {
+            assert!(
+                ActionTemplate::parse(template).is_err(),
+                "{} should be rejected",
+                template
+            );
+        }
+    }
+}
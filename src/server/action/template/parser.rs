@@ -0,0 +1,385 @@
+//! Parser of the template language.
+//!
+//! ```text
+//! template    = { text | placeholder | if_block | raw_block }
+//! placeholder = "{{" expression "}}"
+//! expression  = source { "|" filter }
+//! source      = json_path | "vars." name | "params." name
+//! filter      = name [ "(" [ literal { "," literal } ] ")" ]
+//! if_block    = "{%" "if" condition "%}" template
+//!               [ "{%" "else" "%}" template ] "{%" "endif" "%}"
+//! raw_block   = "{%" "raw" "%}" text "{%" "endraw" "%}"
+//! condition   = [ "not" ] expression [ ( "==" | "!=" ) literal ]
+//! literal     = string | number | "true" | "false" | "null"
+//! ```
+
+use serde_json::Value;
+use serde_json_path::JsonPath;
+
+use super::filter::Filter;
+use super::{Comparison, Condition, Expression, Node, Source, VALUE_PREFIXES};
+
+/// Parses the template into its nodes
+pub fn parse(src: &str) -> Result<Vec<Node>, String> {
+    let mut parser = Parser { src, pos: 0 };
+    let (nodes, end) = parser.parse_nodes()?;
+
+    match end {
+        None => Ok(nodes),
+        Some((tag, pos)) => Err(format!(
+            "unexpected '{}' at position {}",
+            tag,
+            parser.position(pos)
+        )),
+    }
+}
+
+/// An `else` or `endif` tag with its position
+type EndTag = (&'static str, usize);
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    /// Parses nodes until the end of the template or an `else` or `endif` tag
+    /// which is returned with its position
+    fn parse_nodes(&mut self) -> Result<(Vec<Node>, Option<EndTag>), String> {
+        let mut nodes = Vec::new();
+
+        loop {
+            let rest = &self.src[self.pos..];
+            let next_tag = [rest.find("{{"), rest.find("{%")]
+                .iter()
+                .flatten()
+                .min()
+                .copied();
+            let Some(offset) = next_tag else {
+                if !rest.is_empty() {
+                    nodes.push(Node::Text(rest.to_owned()));
+                }
+                self.pos = self.src.len();
+                return Ok((nodes, None));
+            };
+            if offset > 0 {
+                nodes.push(Node::Text(rest[..offset].to_owned()));
+            }
+            let start = self.pos + offset;
+            self.pos = start + 2;
+
+            if rest[offset..].starts_with("{{") {
+                let expression = self.parse_expression()?;
+                self.expect("}}")?;
+                nodes.push(Node::Placeholder(expression));
+                continue;
+            }
+            self.skip_whitespace();
+            match self.parse_name() {
+                "if" => {
+                    let condition = self.parse_condition()?;
+                    self.expect("%}")?;
+                    let (then, end) = self.parse_nodes()?;
+                    let otherwise = match end {
+                        Some(("endif", _)) => Vec::new(),
+                        Some(("else", _)) => match self.parse_nodes()? {
+                            (otherwise, Some(("endif", _))) => otherwise,
+                            (_, Some((tag, pos))) => {
+                                return Err(format!(
+                                    "unexpected '{}' at position {}",
+                                    tag,
+                                    self.position(pos)
+                                ))
+                            }
+                            (_, None) => return Err(self.unclosed_if(start)),
+                        },
+                        _ => return Err(self.unclosed_if(start)),
+                    };
+                    nodes.push(Node::If {
+                        condition,
+                        then,
+                        otherwise,
+                    });
+                }
+                "raw" => {
+                    self.expect("%}")?;
+                    nodes.push(Node::Text(self.parse_raw(start)?.to_owned()));
+                }
+                "else" => {
+                    self.expect("%}")?;
+                    return Ok((nodes, Some(("else", start))));
+                }
+                "endif" => {
+                    self.expect("%}")?;
+                    return Ok((nodes, Some(("endif", start))));
+                }
+                "" => {
+                    return Err(format!(
+                        "expected a tag at position {}",
+                        self.position(self.pos)
+                    ))
+                }
+                name => {
+                    return Err(format!(
+                        "unknown tag '{}' at position {}",
+                        name,
+                        self.position(start)
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Returns the text until the next `endraw` tag
+    fn parse_raw(&mut self, start: usize) -> Result<&'a str, String> {
+        let text_start = self.pos;
+
+        while let Some(offset) = self.rest().find("{%") {
+            let tag_start = self.pos + offset;
+            self.pos = tag_start + 2;
+            self.skip_whitespace();
+            if self.parse_name() == "endraw" && self.expect("%}").is_ok() {
+                return Ok(&self.src[text_start..tag_start]);
+            }
+        }
+
+        Err(format!(
+            "missing '{{% endraw %}}' for the raw block at position {}",
+            self.position(start)
+        ))
+    }
+
+    fn parse_condition(&mut self) -> Result<Condition, String> {
+        self.skip_whitespace();
+        let negated =
+            self.rest().starts_with("not") && self.rest()[3..].starts_with(char::is_whitespace);
+        if negated {
+            self.pos += 3;
+        }
+        let expression = self.parse_expression()?;
+        self.skip_whitespace();
+        let comparison = if self.rest().starts_with("==") {
+            self.pos += 2;
+            Some(Comparison::Equal(self.parse_literal()?))
+        } else if self.rest().starts_with("!=") {
+            self.pos += 2;
+            Some(Comparison::NotEqual(self.parse_literal()?))
+        } else {
+            None
+        };
+
+        Ok(Condition {
+            negated,
+            expression,
+            comparison,
+        })
+    }
+
+    fn parse_expression(&mut self) -> Result<Expression, String> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let source = if self.rest().starts_with('$') {
+            let query = self.parse_path()?;
+            let path = JsonPath::parse(query).map_err(|e| {
+                format!(
+                    "invalid json path '{}' at position {}: {}",
+                    query,
+                    self.position(start),
+                    e
+                )
+            })?;
+            Source::Path(path)
+        } else {
+            let name = self.parse_name();
+            if !VALUE_PREFIXES
+                .iter()
+                .any(|prefix| name.len() > prefix.len() && name.starts_with(prefix))
+            {
+                return Err(format!(
+                    "expected a json path or a variable at position {}",
+                    self.position(start)
+                ));
+            }
+            Source::Named(name.to_owned())
+        };
+        let mut filters = Vec::new();
+
+        loop {
+            self.skip_whitespace();
+            if !self.rest().starts_with('|') {
+                break;
+            }
+            self.pos += 1;
+            self.skip_whitespace();
+            let filter_start = self.pos;
+            let name = self.parse_name();
+            if name.is_empty() {
+                return Err(format!(
+                    "expected a filter at position {}",
+                    self.position(filter_start)
+                ));
+            }
+            self.skip_whitespace();
+            let args = if self.rest().starts_with('(') {
+                self.parse_args()?
+            } else {
+                Vec::new()
+            };
+            let filter = Filter::new(name, args)
+                .map_err(|e| format!("{} at position {}", e, self.position(filter_start)))?;
+            filters.push(filter);
+        }
+
+        Ok(Expression {
+            text: self.src[start..self.pos].trim().to_owned(),
+            source,
+            filters,
+        })
+    }
+
+    /// Parses a json path until whitespace, a filter or the end of the tag
+    /// outside of brackets and quotes
+    fn parse_path(&mut self) -> Result<&'a str, String> {
+        let start = self.pos;
+        let mut depth = 0usize;
+        let mut quote = None;
+
+        for (offset, c) in self.src[start..].char_indices() {
+            let rest = &self.src[start + offset..];
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), _) => {}
+                (None, '\'' | '"') => quote = Some(c),
+                (None, '[' | '(') => depth += 1,
+                (None, ']' | ')') => depth = depth.saturating_sub(1),
+                (None, c)
+                    if depth == 0
+                        && (c.is_whitespace()
+                            || c == '|'
+                            || ["}}", "%}", "==", "!="].iter().any(|t| rest.starts_with(t))) =>
+                {
+                    self.pos = start + offset;
+                    return Ok(&self.src[start..self.pos]);
+                }
+                _ => {}
+            }
+        }
+
+        Err(format!(
+            "unterminated json path at position {}",
+            self.position(start)
+        ))
+    }
+
+    fn parse_args(&mut self) -> Result<Vec<Value>, String> {
+        self.expect("(")?;
+        let mut args = Vec::new();
+        self.skip_whitespace();
+        if self.rest().starts_with(')') {
+            self.pos += 1;
+            return Ok(args);
+        }
+
+        loop {
+            args.push(self.parse_literal()?);
+            self.skip_whitespace();
+            if self.rest().starts_with(',') {
+                self.pos += 1;
+            } else {
+                self.expect(")")?;
+                return Ok(args);
+            }
+        }
+    }
+
+    fn parse_literal(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let mut chars = self.rest().char_indices();
+
+        match chars.next() {
+            Some((_, quote @ ('"' | '\''))) => {
+                let mut value = String::new();
+                while let Some((offset, c)) = chars.next() {
+                    match c {
+                        '\\' => match chars.next() {
+                            Some((_, 'n')) => value.push('\n'),
+                            Some((_, c)) => value.push(c),
+                            None => break,
+                        },
+                        c if c == quote => {
+                            self.pos = start + offset + 1;
+                            return Ok(Value::String(value));
+                        }
+                        c => value.push(c),
+                    }
+                }
+                Err(format!(
+                    "unterminated string at position {}",
+                    self.position(start)
+                ))
+            }
+            _ => {
+                let end = self
+                    .rest()
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '.' || c == '-'))
+                    .unwrap_or(self.rest().len());
+                let literal = &self.rest()[..end];
+                let value = serde_json::from_str::<Value>(literal)
+                    .ok()
+                    .filter(|v| !v.is_object() && !v.is_array() && !literal.is_empty())
+                    .ok_or_else(|| {
+                        format!("expected a literal at position {}", self.position(start))
+                    })?;
+                self.pos += end;
+                Ok(value)
+            }
+        }
+    }
+
+    /// Parses a name consisting of alphanumeric characters, `_` and `.`
+    fn parse_name(&mut self) -> &'a str {
+        let rest = &self.src[self.pos..];
+        let end = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+            .unwrap_or(rest.len());
+        self.pos += end;
+
+        &rest[..end]
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            Ok(())
+        } else {
+            Err(format!(
+                "expected '{}' at position {}",
+                token,
+                self.position(self.pos)
+            ))
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn unclosed_if(&self, pos: usize) -> String {
+        format!(
+            "missing '{{% endif %}}' for the if at position {}",
+            self.position(pos)
+        )
+    }
+
+    /// Returns the character position starting at 1 for error messages
+    fn position(&self, pos: usize) -> usize {
+        self.src[..pos].chars().count() + 1
+    }
+}
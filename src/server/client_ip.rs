@@ -0,0 +1,293 @@
+use crate::utils::error::{MultihookError, MultihookResult};
+use crate::utils::settings::ForwardedHeader;
+use hyper::header::{HeaderName, FORWARDED};
+use hyper::HeaderMap;
+use ipnet::IpNet;
+use std::net::{IpAddr, SocketAddr};
+
+static X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+/// The resolved address of the client that sent a request.
+/// It is stored in the request extensions.
+#[derive(Clone, Copy, Debug)]
+pub struct ClientIp(pub IpAddr);
+
+/// Determines which forwarding headers are trusted
+#[derive(Clone, Debug, Default)]
+pub struct ProxySettings {
+    pub trusted_proxies: Vec<IpNet>,
+    pub header: ForwardedHeader,
+    /// Requests over unix sockets have no peer address and are only
+    /// resolved with their forwarding header if this is enabled
+    pub trust_unix_sockets: bool,
+}
+
+/// Resolves the address of the client. The configured forwarding header is only honoured
+/// when the request was sent by one of the trusted proxies or over a trusted unix socket
+/// (where the peer has no address).
+/// The chain of forwarded addresses is walked from right to left and
+/// the first address that doesn't belong to a trusted proxy is returned.
+pub fn resolve_client_ip(
+    peer: Option<IpAddr>,
+    headers: &HeaderMap,
+    proxies: &ProxySettings,
+) -> Option<IpAddr> {
+    let trusted_proxies = &proxies.trusted_proxies;
+    match peer {
+        Some(peer) if !is_in_ranges(&peer, trusted_proxies) => return Some(peer),
+        None if !proxies.trust_unix_sockets => return None,
+        _ => {}
+    }
+    let chain = forwarded_chain(headers, proxies.header);
+
+    chain
+        .iter()
+        .rev()
+        .find(|ip| !is_in_ranges(ip, trusted_proxies))
+        .or_else(|| chain.first())
+        .copied()
+        .or(peer)
+}
+
+/// Checks if the client is allowed to access the service.
+/// Clients with an unknown address are always rejected.
+pub fn check_allowed(client_ip: Option<IpAddr>, ranges: &[IpNet]) -> MultihookResult<()> {
+    match client_ip {
+        Some(ip) if is_in_ranges(&ip, ranges) => Ok(()),
+        Some(ip) => Err(MultihookError::ForbiddenAddress(ip)),
+        None => Err(MultihookError::UnknownAddress),
+    }
+}
+
+/// Returns if the address is contained in any of the given ranges
+pub fn is_in_ranges(ip: &IpAddr, ranges: &[IpNet]) -> bool {
+    let ip = match ip {
+        IpAddr::V6(v6) => v6
+            .to_ipv4_mapped()
+            .map(IpAddr::V4)
+            .unwrap_or(IpAddr::V6(*v6)),
+        v4 => *v4,
+    };
+    ranges.iter().any(|range| range.contains(&ip))
+}
+
+/// Returns the list of forwarded addresses of the given header.
+/// Other forwarding headers are ignored since proxies pass them through unchanged
+fn forwarded_chain(headers: &HeaderMap, header: ForwardedHeader) -> Vec<IpAddr> {
+    match header {
+        ForwardedHeader::Forwarded => forwarded_header_chain(headers),
+        ForwardedHeader::XForwardedFor => headers
+            .get_all(&X_FORWARDED_FOR)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .filter_map(parse_forwarded_ip)
+            .collect(),
+    }
+}
+
+/// Returns the `for` addresses of the RFC 7239 `Forwarded` header
+fn forwarded_header_chain(headers: &HeaderMap) -> Vec<IpAddr> {
+    headers
+        .get_all(FORWARDED)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|element| {
+            element.split(';').find_map(|pair| {
+                let (key, value) = pair.trim().split_once('=')?;
+                if key.eq_ignore_ascii_case("for") {
+                    parse_forwarded_ip(value)
+                } else {
+                    None
+                }
+            })
+        })
+        .collect()
+}
+
+/// Parses a forwarded address that might be quoted, bracketed and contain a port
+fn parse_forwarded_ip(value: &str) -> Option<IpAddr> {
+    let value = value.trim().trim_matches('"');
+
+    if let Ok(ip) = value.parse() {
+        return Some(ip);
+    }
+    if let Ok(addr) = value.parse::<SocketAddr>() {
+        return Some(addr.ip());
+    }
+
+    value
+        .strip_prefix('[')
+        .and_then(|v| v.strip_suffix(']'))
+        .and_then(|v| v.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::HeaderValue;
+
+    fn proxies(header: ForwardedHeader) -> ProxySettings {
+        ProxySettings {
+            trusted_proxies: vec!["10.0.0.0/8".parse().unwrap(), "fd00::/8".parse().unwrap()],
+            header,
+            trust_unix_sockets: false,
+        }
+    }
+
+    fn headers(name: &HeaderName, values: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    fn ip(ip: &str) -> Option<IpAddr> {
+        Some(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn it_ignores_forwarded_addresses_of_untrusted_peers() {
+        let headers = headers(&X_FORWARDED_FOR, &["10.1.2.3"]);
+        let proxies = proxies(ForwardedHeader::XForwardedFor);
+
+        assert_eq!(
+            resolve_client_ip(ip("203.0.113.7"), &headers, &proxies),
+            ip("203.0.113.7")
+        );
+    }
+
+    #[test]
+    fn it_walks_the_chain_through_trusted_proxies() {
+        let headers = headers(
+            &X_FORWARDED_FOR,
+            &["192.0.2.66, 198.51.100.1", "10.0.0.2, 10.0.0.3"],
+        );
+        let proxies = proxies(ForwardedHeader::XForwardedFor);
+
+        // the spoofed first address is skipped since the last untrusted hop is the client
+        assert_eq!(
+            resolve_client_ip(ip("10.0.0.1"), &headers, &proxies),
+            ip("198.51.100.1")
+        );
+    }
+
+    #[test]
+    fn it_returns_the_first_address_if_all_are_trusted() {
+        let headers = headers(&X_FORWARDED_FOR, &["10.0.0.5, 10.0.0.6"]);
+        let proxies = proxies(ForwardedHeader::XForwardedFor);
+
+        assert_eq!(
+            resolve_client_ip(ip("10.0.0.1"), &headers, &proxies),
+            ip("10.0.0.5")
+        );
+        assert_eq!(
+            resolve_client_ip(ip("10.0.0.1"), &HeaderMap::new(), &proxies),
+            ip("10.0.0.1")
+        );
+    }
+
+    #[test]
+    fn it_only_reads_the_configured_header() {
+        let mut headers = headers(&X_FORWARDED_FOR, &["198.51.100.1"]);
+        headers.insert(FORWARDED, HeaderValue::from_static("for=192.0.2.66"));
+
+        assert_eq!(
+            resolve_client_ip(
+                ip("10.0.0.1"),
+                &headers,
+                &proxies(ForwardedHeader::XForwardedFor)
+            ),
+            ip("198.51.100.1")
+        );
+        assert_eq!(
+            resolve_client_ip(
+                ip("10.0.0.1"),
+                &headers,
+                &proxies(ForwardedHeader::Forwarded)
+            ),
+            ip("192.0.2.66")
+        );
+    }
+
+    #[test]
+    fn it_parses_the_forwarded_header() {
+        let headers = headers(
+            &FORWARDED,
+            &[
+                r#"for=192.0.2.60;proto=http;by=203.0.113.43, For="[2001:db8:cafe::17]:4711""#,
+                r#"for="198.51.100.17:8080";host=example.com, for=unknown, for="_hidden""#,
+            ],
+        );
+
+        assert_eq!(
+            forwarded_header_chain(&headers),
+            vec![
+                "192.0.2.60".parse::<IpAddr>().unwrap(),
+                "2001:db8:cafe::17".parse().unwrap(),
+                "198.51.100.17".parse().unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_parses_forwarded_addresses() {
+        assert_eq!(parse_forwarded_ip(" 192.0.2.1 "), ip("192.0.2.1"));
+        assert_eq!(parse_forwarded_ip("192.0.2.1:443"), ip("192.0.2.1"));
+        assert_eq!(parse_forwarded_ip("\"2001:db8::1\""), ip("2001:db8::1"));
+        assert_eq!(parse_forwarded_ip("[2001:db8::1]"), ip("2001:db8::1"));
+        assert_eq!(
+            parse_forwarded_ip("\"[2001:db8::1]:80\""),
+            ip("2001:db8::1")
+        );
+        assert_eq!(parse_forwarded_ip("unknown"), None);
+        assert_eq!(parse_forwarded_ip(""), None);
+    }
+
+    #[test]
+    fn it_matches_ipv4_mapped_peers() {
+        let headers = headers(&X_FORWARDED_FOR, &["198.51.100.1"]);
+        let proxies = proxies(ForwardedHeader::XForwardedFor);
+
+        assert_eq!(
+            resolve_client_ip(ip("::ffff:10.0.0.1"), &headers, &proxies),
+            ip("198.51.100.1")
+        );
+        assert_eq!(
+            resolve_client_ip(ip("::ffff:203.0.113.7"), &headers, &proxies),
+            ip("::ffff:203.0.113.7")
+        );
+        assert!(check_allowed(ip("::ffff:10.0.0.1"), &proxies.trusted_proxies).is_ok());
+    }
+
+    #[test]
+    fn it_only_trusts_unix_sockets_if_enabled() {
+        let headers = headers(&X_FORWARDED_FOR, &["198.51.100.1"]);
+        let mut proxies = proxies(ForwardedHeader::XForwardedFor);
+
+        assert_eq!(resolve_client_ip(None, &headers, &proxies), None);
+        proxies.trust_unix_sockets = true;
+        assert_eq!(
+            resolve_client_ip(None, &headers, &proxies),
+            ip("198.51.100.1")
+        );
+        assert_eq!(resolve_client_ip(None, &HeaderMap::new(), &proxies), None);
+    }
+
+    #[test]
+    fn it_rejects_unknown_addresses() {
+        let ranges = vec!["10.0.0.0/8".parse().unwrap()];
+
+        assert!(check_allowed(ip("10.1.1.1"), &ranges).is_ok());
+        assert!(matches!(
+            check_allowed(ip("192.0.2.1"), &ranges),
+            Err(MultihookError::ForbiddenAddress(_))
+        ));
+        assert!(matches!(
+            check_allowed(None, &ranges),
+            Err(MultihookError::UnknownAddress)
+        ));
+    }
+}
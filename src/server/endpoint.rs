@@ -1,24 +1,64 @@
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
 
-use crate::secret_validation::SecretValidator;
+use crate::server::route::PathParams;
+use crate::server::tls::ClientIdentity;
 use crate::utils::error::{LogErr, MultihookError, MultihookResult};
-use crate::utils::settings::{EndpointSettings, SecretSettings, Settings};
+use crate::utils::settings::{
+    BodyFormat, ClientCertificateSettings, EndpointSettings, Hooks, SecretSettings, Settings,
+};
+use hyper::body::HttpBody;
+use hyper::header::CONTENT_TYPE;
 use hyper::http::request::Parts;
-use hyper::{Body, Request};
+use hyper::{Body, HeaderMap, Method, Request};
+use ipnet::IpNet;
 use serde_json::Value;
 
 use super::action::Action;
+use super::jobs;
+use super::payload;
 
 #[derive(Clone)]
 pub struct HookEndpoint {
     name: String,
     action: Action,
+    methods: Vec<Method>,
+    body_format: BodyFormat,
+    strict_placeholders: bool,
     global_hooks: ActionHooks,
     hooks: ActionHooks,
     run_detached: bool,
+    timeout: Option<u64>,
+    max_body_size: Option<usize>,
+    allowed_ips: Option<Vec<IpNet>>,
+    client_certificate: Option<ClientCertificateSettings>,
     secret: Option<SecretSettings>,
 }
 
+/// A request to execute an endpoint from the command line
+#[derive(Default)]
+pub struct LocalRequest {
+    pub body: Vec<u8>,
+    pub headers: HeaderMap,
+    /// Validate the configured secret against the headers and body
+    pub verify_secret: bool,
+    /// Print the rendered commands instead of executing them
+    pub dry_run: bool,
+    /// The values of the path parameters
+    pub params: HashMap<String, String>,
+}
+
+/// The request data the actions are executed with
+#[derive(Clone)]
+struct HookInput {
+    /// The raw body that is passed in `HOOK_BODY`
+    body: String,
+    /// The body converted to json that the templates are rendered with
+    data: Value,
+    params: HashMap<String, String>,
+}
+
 #[derive(Clone, Default)]
 struct ActionHooks {
     pre: Option<Action>,
@@ -27,62 +67,106 @@ struct ActionHooks {
 }
 
 impl HookEndpoint {
+    /// Creates the endpoint from its config. Fails if any of its actions is invalid
     pub fn from_config<S: Into<String>>(
         name: S,
         global: &Settings,
         endpoint: &EndpointSettings,
-    ) -> Self {
-        let global_hooks = global
-            .hooks
-            .as_ref()
-            .map(|hooks_cfg| ActionHooks {
-                pre: hooks_cfg.pre_action.as_ref().map(|a| Action::new(a, true)),
-                post: hooks_cfg.post_action.as_ref().map(|a| Action::new(a, true)),
-                error: hooks_cfg.err_action.as_ref().map(|a| Action::new(a, true)),
-            })
-            .unwrap_or_default();
-
-        let hooks = endpoint
-            .hooks
-            .as_ref()
-            .map(|hooks_cfg| ActionHooks {
-                pre: hooks_cfg
-                    .pre_action
-                    .as_ref()
-                    .map(|a| Action::new(a, endpoint.allow_parallel)),
-                post: hooks_cfg
-                    .post_action
+    ) -> MultihookResult<Self> {
+        let action = |command: &String, allow_parallel: bool| {
+            Action::new(command, allow_parallel)
+                .map(|action| action.variables(endpoint.variables.clone()))
+        };
+        let hooks = |hooks: Option<&Hooks>, allow_parallel: bool| {
+            let Some(hooks) = hooks else {
+                return Ok(ActionHooks::default());
+            };
+            let hook = |command: &Option<String>| {
+                command
                     .as_ref()
-                    .map(|a| Action::new(a, endpoint.allow_parallel)),
-                error: hooks_cfg
-                    .err_action
-                    .as_ref()
-                    .map(|a| Action::new(a, endpoint.allow_parallel)),
+                    .map(|command| action(command, allow_parallel))
+                    .transpose()
+            };
+
+            MultihookResult::Ok(ActionHooks {
+                pre: hook(&hooks.pre_action)?,
+                post: hook(&hooks.post_action)?,
+                error: hook(&hooks.err_action)?,
             })
-            .unwrap_or_default();
+        };
 
-        Self {
+        Ok(Self {
             name: name.into(),
-            action: Action::new(&endpoint.action, endpoint.allow_parallel),
+            action: action(&endpoint.action, endpoint.allow_parallel)?,
+            methods: endpoint
+                .methods
+                .as_ref()
+                .map(|methods| {
+                    methods
+                        .iter()
+                        .filter_map(|method| parse_method(method).ok())
+                        .collect()
+                })
+                .unwrap_or_else(|| vec![Method::POST]),
+            body_format: endpoint.body_format,
+            strict_placeholders: endpoint.strict_placeholders,
             run_detached: endpoint.run_detached,
+            timeout: endpoint.timeout,
+            max_body_size: global.server.max_body_size,
+            allowed_ips: endpoint.allowed_ips.clone(),
+            client_certificate: endpoint.client_certificate.clone(),
             secret: endpoint.secret.clone(),
-            global_hooks,
-            hooks,
-        }
+            global_hooks: hooks(global.hooks.as_ref(), true)?,
+            hooks: hooks(endpoint.hooks.as_ref(), endpoint.allow_parallel)?,
+        })
+    }
+
+    /// Returns the http methods the endpoint can be called with
+    pub fn methods(&self) -> &[Method] {
+        &self.methods
+    }
+
+    /// Returns the address ranges that are allowed to call this endpoint
+    pub fn allowed_ips(&self) -> Option<&[IpNet]> {
+        self.allowed_ips.as_deref()
     }
 
     pub async fn execute(&self, req: Request<Body>) -> MultihookResult<()> {
+        let job = jobs::start_job(&self.name);
         let (parts, body) = req.into_parts();
-        let body = hyper::body::to_bytes(body).await?.to_vec();
+        let identity = parts.extensions.get::<Arc<ClientIdentity>>();
+        self.validate_client(identity.map(Arc::as_ref))?;
 
+        let body = self.read_body(body).await?;
         self.validate_secret(&parts, &body)?;
-        let body = String::from_utf8(body)?;
+        let params = parts
+            .extensions
+            .get::<PathParams>()
+            .map(|PathParams(params)| params.clone())
+            .unwrap_or_default();
+        let input = self
+            .parse_input(&parts.headers, parts.uri.query(), body, params)
+            .await?;
+        self.check_placeholders(&input)?;
+
+        let mut env = HashMap::new();
+        if let Some(identity) = identity {
+            env.insert(
+                String::from("HOOK_CLIENT_IDENTITY"),
+                identity.name().to_owned(),
+            );
+            env.insert(
+                String::from("HOOK_CLIENT_SUBJECT"),
+                identity.subject.to_owned(),
+            );
+        }
 
         if self.run_detached {
             tokio::spawn({
                 let action = self.clone();
                 async move {
-                    if let Err(e) = action.execute_command(&body).await {
+                    let _job = job;
+                    if let Err(e) = action.execute_command(&input, env).await {
                         log::error!("Detached hook threw an error: {:?}", e);
                     }
                 }
@@ -90,65 +174,247 @@ impl HookEndpoint {
 
             Ok(())
         } else {
-            self.execute_command(&body).await
+            self.execute_with_timeout(&input, env).await
+        }
+    }
+
+    /// Executes the endpoint without an http request.
+    /// Client validation is skipped and secret validation is optional.
+    pub async fn execute_local(&self, request: LocalRequest) -> MultihookResult<()> {
+        if request.verify_secret {
+            let (mut parts, _) = Request::new(()).into_parts();
+            parts.headers = request.headers.clone();
+            self.validate_secret(&parts, &request.body)?;
+        }
+        let input = self
+            .parse_input(&request.headers, None, request.body, request.params)
+            .await?;
+        self.check_placeholders(&input)?;
+
+        if request.dry_run {
+            self.print_commands(&input, HashMap::new());
+            Ok(())
+        } else {
+            self.execute_with_timeout(&input, HashMap::new()).await
+        }
+    }
+
+    /// Converts the body to json according to its content type.
+    /// Requests without a body use their query parameters instead unless a json body is required
+    async fn parse_input(
+        &self,
+        headers: &HeaderMap,
+        query: Option<&str>,
+        body: Vec<u8>,
+        params: HashMap<String, String>,
+    ) -> MultihookResult<HookInput> {
+        let query_data = matches!(self.body_format, BodyFormat::Any | BodyFormat::Form);
+        if let (true, true, Some(query)) = (query_data, body.is_empty(), query) {
+            let secret_param = self
+                .secret
+                .as_ref()
+                .and_then(|secret| secret.format.query_param());
+            let data = payload::form_to_json(query.as_bytes(), secret_param);
+
+            return Ok(HookInput {
+                body: data.to_string(),
+                data,
+                params,
+            });
+        }
+        let content_type = headers.get(CONTENT_TYPE).and_then(|c| c.to_str().ok());
+        let data = payload::parse_body(self.body_format, content_type, &body)
+            .await
+            .map_err(MultihookError::InvalidBody)?;
+        // multipart bodies can contain binary files
+        let multipart = content_type
+            .map(payload::mime_type)
+            .is_some_and(|m| m.starts_with("multipart/"));
+        let body = if multipart {
+            String::from_utf8_lossy(&body).into_owned()
+        } else {
+            String::from_utf8(body)?
+        };
+
+        Ok(HookInput { body, data, params })
+    }
+
+    /// Fails if any placeholder of the actions rejects its value or, with
+    /// strict placeholders enabled, resolves to an empty value
+    fn check_placeholders(&self, input: &HookInput) -> MultihookResult<()> {
+        let hooks = [&self.global_hooks, &self.hooks];
+        let actions = hooks
+            .iter()
+            .flat_map(|hooks| [&hooks.pre, &hooks.post, &hooks.error])
+            .flatten()
+            .chain([&self.action]);
+
+        for action in actions {
+            action.check_placeholders(&input.data, &input.params, self.strict_placeholders)?;
+        }
+
+        Ok(())
+    }
+
+    /// Prints the rendered commands and the environment instead of executing them
+    fn print_commands(&self, input: &HookInput, mut env: HashMap<String, String>) {
+        self.insert_env(&mut env, input);
+        let mut env = env.into_iter().collect::<Vec<_>>();
+        env.sort();
+
+        println!("Environment:");
+        for (key, value) in env {
+            println!("  {}={}", key, value);
+        }
+        let actions = [
+            ("Global Pre-Hook", self.global_hooks.pre.as_ref()),
+            ("Endpoint Pre-Hook", self.hooks.pre.as_ref()),
+            ("Action", Some(&self.action)),
+            ("Global Post-Hook", self.global_hooks.post.as_ref()),
+            ("Endpoint Post-Hook", self.hooks.post.as_ref()),
+            (
+                "Global Error-Hook (on failure)",
+                self.global_hooks.error.as_ref(),
+            ),
+            (
+                "Endpoint Error-Hook (on failure)",
+                self.hooks.error.as_ref(),
+            ),
+        ];
+        for (label, action) in actions {
+            if let Some(action) = action {
+                match action.render(&input.data, &input.params) {
+                    Ok(command) => println!("{}:\n  {}", label, command),
+                    Err(e) => println!("{}:\n  {}", label, e),
+                }
+            }
+        }
+    }
+
+    /// Executes the actions and fails if they don't finish within the configured timeout
+    async fn execute_with_timeout(
+        &self,
+        input: &HookInput,
+        env: HashMap<String, String>,
+    ) -> MultihookResult<()> {
+        let Some(timeout) = self.timeout else {
+            return self.execute_command(input, env).await;
+        };
+        let command = self.execute_command(input, env.clone());
+
+        match tokio::time::timeout(Duration::from_secs(timeout), command).await {
+            Ok(result) => result,
+            Err(_) => {
+                // the process group of the action is terminated when its future is dropped
+                let error = MultihookError::Timeout(timeout);
+                let mut env = env;
+                self.insert_env(&mut env, input);
+                self.run_error_hooks(input, env, &error).await;
+
+                Err(error)
+            }
+        }
+    }
+
+    /// Reads the body while enforcing the configured size limit
+    async fn read_body(&self, mut body: Body) -> MultihookResult<Vec<u8>> {
+        let Some(limit) = self.max_body_size else {
+            return Ok(hyper::body::to_bytes(body).await?.to_vec());
+        };
+        if body.size_hint().lower() > limit as u64 {
+            return Err(MultihookError::PayloadTooLarge(limit));
+        }
+        let mut bytes = Vec::new();
+
+        while let Some(chunk) = body.data().await {
+            let chunk = chunk?;
+            if bytes.len() + chunk.len() > limit {
+                return Err(MultihookError::PayloadTooLarge(limit));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+
+        Ok(bytes)
+    }
+
+    /// Validates the verified client certificate against the allowed subjects and alternative names
+    fn validate_client(&self, identity: Option<&ClientIdentity>) -> MultihookResult<()> {
+        let Some(rules) = &self.client_certificate else {
+            return Ok(());
+        };
+        let Some(identity) = identity else {
+            log::error!("Missing Client Certificate");
+            return Err(MultihookError::MissingSecret);
+        };
+        let subject_allowed = rules.subjects.iter().any(|subject| {
+            *subject == identity.subject || Some(subject) == identity.common_name.as_ref()
+        });
+        let alt_name_allowed = rules
+            .alt_names
+            .iter()
+            .any(|name| identity.alt_names.contains(name));
+
+        if subject_allowed || alt_name_allowed {
+            Ok(())
+        } else {
+            Err(MultihookError::UntrustedClient(identity.name().to_owned()))
         }
     }
 
-    fn validate_secret(&self, parts: &Parts, body: &Vec<u8>) -> MultihookResult<()> {
+    fn validate_secret(&self, parts: &Parts, body: &[u8]) -> MultihookResult<()> {
         if let Some(secret) = &self.secret {
             let validator = secret.format.validator();
-            if !validator.validate(&parts.headers, &body, &secret.value.as_bytes()) {
-                return Err(MultihookError::InvalidSecret);
-            }
+            validator.validate(parts, body, secret.value.as_bytes())?;
         }
         Ok(())
     }
 
-    async fn execute_command(&self, body: &str) -> MultihookResult<()> {
-        let json_body: Value = serde_json::from_str(body).unwrap_or_default();
-        let mut env = HashMap::new();
-        env.insert("HOOK_NAME", self.name.to_owned());
-        env.insert("HOOK_BODY", body.to_string());
+    /// Adds the environment variables that are passed to every action.
+    /// Path parameters are passed as `HOOK_PARAM_<NAME>`
+    fn insert_env(&self, env: &mut HashMap<String, String>, input: &HookInput) {
+        env.insert(String::from("HOOK_NAME"), self.name.to_owned());
+        env.insert(String::from("HOOK_BODY"), input.body.to_owned());
+        for (name, value) in &input.params {
+            env.insert(
+                format!("HOOK_PARAM_{}", name.to_uppercase()),
+                value.to_owned(),
+            );
+        }
+    }
+
+    async fn execute_command(
+        &self,
+        input: &HookInput,
+        mut env: HashMap<String, String>,
+    ) -> MultihookResult<()> {
+        self.insert_env(&mut env, input);
 
         if let Some(global_pre) = &self.global_hooks.pre {
             global_pre
-                .run(&json_body, &env)
+                .run(&input.data, &input.params, &env)
                 .await
                 .log_err("Global Pre-Hook failed {e}");
         }
         if let Some(pre_hook) = &self.hooks.pre {
             pre_hook
-                .run(&json_body, &env)
+                .run(&input.data, &input.params, &env)
                 .await
                 .log_err("Endpoint Pre-Hook failed {e}");
         }
-        if let Err(e) = self.action.run(&json_body, &env).await {
-            env.insert("HOOK_ERROR", format!("{e}"));
-
-            if let Some(global_err_action) = &self.global_hooks.error {
-                global_err_action
-                    .run(&json_body, &env)
-                    .await
-                    .log_err("Global Error-Hook failed {e}");
-            }
-            if let Some(err_hook) = &self.hooks.error {
-                err_hook
-                    .run(&json_body, &env)
-                    .await
-                    .log_err("Endpoint Error-Hook failed");
-            }
+        if let Err(e) = self.action.run(&input.data, &input.params, &env).await {
+            self.run_error_hooks(input, env, &e).await;
 
             Err(e)
         } else {
             if let Some(global_post_hook) = &self.global_hooks.post {
                 global_post_hook
-                    .run(&json_body, &env)
+                    .run(&input.data, &input.params, &env)
                     .await
                     .log_err("Global Post-Hook failed");
             }
             if let Some(post_hook) = &self.hooks.post {
                 post_hook
-                    .run(&json_body, &env)
+                    .run(&input.data, &input.params, &env)
                     .await
                     .log_err("Endpoint Post-Hook failed")
             }
@@ -156,4 +422,47 @@ impl HookEndpoint {
             Ok(())
         }
     }
+
+    /// Runs the error hooks with the error in `HOOK_ERROR`
+    async fn run_error_hooks(
+        &self,
+        input: &HookInput,
+        mut env: HashMap<String, String>,
+        error: &MultihookError,
+    ) {
+        env.insert(String::from("HOOK_ERROR"), format!("{error}"));
+
+        if let Some(global_err_action) = &self.global_hooks.error {
+            global_err_action
+                .run(&input.data, &input.params, &env)
+                .await
+                .log_err("Global Error-Hook failed {e}");
+        }
+        if let Some(err_hook) = &self.hooks.error {
+            err_hook
+                .run(&input.data, &input.params, &env)
+                .await
+                .log_err("Endpoint Error-Hook failed");
+        }
+    }
+}
+
+/// Parses an http method. Only the standard methods are accepted
+pub fn parse_method(method: &str) -> Result<Method, String> {
+    let standard = [
+        Method::GET,
+        Method::POST,
+        Method::PUT,
+        Method::DELETE,
+        Method::HEAD,
+        Method::OPTIONS,
+        Method::CONNECT,
+        Method::PATCH,
+        Method::TRACE,
+    ];
+    standard
+        .iter()
+        .find(|m| m.as_str().eq_ignore_ascii_case(method))
+        .cloned()
+        .ok_or_else(|| format!("unknown http method '{}'", method))
 }
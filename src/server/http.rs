@@ -1,23 +1,34 @@
-use crate::utils::error::MultihookResult;
-use hyper::service::{make_service_fn, service_fn};
-use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use crate::server::client_ip::{check_allowed, resolve_client_ip, ClientIp, ProxySettings};
+use crate::server::listener::Listener;
+use crate::server::route::{PathParams, PatternRoutes, RoutePattern};
+use crate::server::tls::ClientIdentity;
+use crate::utils::error::{MultihookError, MultihookResult};
+use crate::utils::settings::ErrorFormat;
+use hyper::header::{HeaderValue, ALLOW, CONTENT_TYPE};
+use hyper::server::conn::Http;
+use hyper::service::service_fn;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use ipnet::IpNet;
 use std::collections::HashMap;
 use std::convert::Infallible;
 use std::future::Future;
-use std::net::{SocketAddr, ToSocketAddrs};
+use std::io;
+use std::net::IpAddr;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::watch;
+use tokio_rustls::TlsAcceptor;
+
+type CallbackFn<T1, T2> = dyn Fn(Request<T1>) -> Pin<Box<dyn Future<Output = MultihookResult<Response<T2>>> + Send + Sync>>
+    + Send
+    + Sync;
 
 pub struct HTTPCallback<T1, T2> {
     methods: Vec<Method>,
-    inner: Arc<
-        dyn Fn(
-                Request<T1>,
-            )
-                -> Pin<Box<dyn Future<Output = MultihookResult<Response<T2>>> + Send + Sync>>
-            + Send
-            + Sync,
-    >,
+    allowed_ips: Option<Vec<IpNet>>,
+    inner: Arc<CallbackFn<T1, T2>>,
 }
 
 impl HTTPCallback<Body, Body> {
@@ -34,9 +45,16 @@ impl HTTPCallback<Body, Body> {
         Self {
             inner: Arc::new(cb),
             methods: Vec::new(),
+            allowed_ips: None,
         }
     }
 
+    pub fn allow_ips(mut self, ranges: Vec<IpNet>) -> Self {
+        self.allowed_ips = Some(ranges);
+
+        self
+    }
+
     pub fn allow_method(mut self, method: Method) -> Self {
         self.methods.push(method);
 
@@ -48,65 +66,316 @@ impl HTTPCallback<Body, Body> {
     }
 
     pub async fn execute(&self, req: Request<Body>) -> MultihookResult<Response<Body>> {
+        if let Some(ranges) = &self.allowed_ips {
+            let client_ip = req.extensions().get::<ClientIp>().map(|ClientIp(ip)| *ip);
+            check_allowed(client_ip, ranges)?;
+        }
         if !self.validate_method(req.method()) {
-            return Ok(Response::builder()
-                .status(StatusCode::METHOD_NOT_ALLOWED)
-                .body(Body::from("Method not allowed"))
-                .unwrap());
+            return Err(MultihookError::MethodNotAllowed(self.methods.clone()));
         }
         self.inner.as_ref()(req).await
     }
 }
 
+/// The callbacks of the routes without parameters and the ones with parameters
+#[derive(Default)]
+pub struct RouteTable {
+    exact: HashMap<String, Arc<HTTPCallback<Body, Body>>>,
+    patterns: PatternRoutes<Arc<HTTPCallback<Body, Body>>>,
+}
+
+impl RouteTable {
+    pub fn insert(
+        &mut self,
+        route: String,
+        pattern: RoutePattern,
+        callback: Arc<HTTPCallback<Body, Body>>,
+    ) {
+        if pattern.is_static() {
+            self.exact.insert(route, callback);
+        } else {
+            self.patterns.insert(route, pattern, callback);
+        }
+    }
+
+    /// Returns the callback of the route matching the path with the captured parameters.
+    /// Routes without parameters take precedence
+    fn find(&self, path: &str) -> Option<(Arc<HTTPCallback<Body, Body>>, PathParams)> {
+        if let Some(callback) = self.exact.get(path) {
+            return Some((Arc::clone(callback), PathParams::default()));
+        }
+        self.patterns
+            .find(path)
+            .map(|(callback, params)| (Arc::clone(callback), params))
+    }
+}
+
+/// Shared handle to the route table that allows replacing all routes at once
+#[derive(Clone, Default)]
+pub struct Routes(Arc<RwLock<RouteTable>>);
+
+impl Routes {
+    /// Atomically replaces all routes
+    pub fn replace(&self, routes: RouteTable) {
+        *self.0.write().unwrap() = routes;
+    }
+
+    fn find(&self, path: &str) -> Option<(Arc<HTTPCallback<Body, Body>>, PathParams)> {
+        self.0.read().unwrap().find(path)
+    }
+}
+
 #[derive(Default)]
 pub struct HTTPServer {
-    routes: HashMap<String, Arc<HTTPCallback<Body, Body>>>,
+    routes: Routes,
+    error_format: ErrorFormat,
+    allowed_ips: Option<Vec<IpNet>>,
+    proxies: ProxySettings,
+    tls_acceptor: Option<TlsAcceptor>,
 }
 
 impl HTTPServer {
-    pub fn add_callback<S: ToString>(&mut self, route: S, cb: HTTPCallback<Body, Body>) {
-        self.routes.insert(route.to_string(), Arc::new(cb));
+    pub fn allow_ips(mut self, ranges: Option<Vec<IpNet>>) -> Self {
+        self.allowed_ips = ranges;
+
+        self
+    }
+
+    pub fn proxies(mut self, proxies: ProxySettings) -> Self {
+        self.proxies = proxies;
+
+        self
+    }
+
+    pub fn error_format(mut self, format: ErrorFormat) -> Self {
+        self.error_format = format;
+
+        self
+    }
+
+    pub fn tls(mut self, acceptor: Option<TlsAcceptor>) -> Self {
+        self.tls_acceptor = acceptor;
+
+        self
     }
 
-    async fn execute_callback(&self, req: Request<Body>) -> Result<Response<Body>, Infallible> {
-        let path = &req.uri().path()[1..];
-        let response = if let Some(cb) = self.routes.get(path) {
+    /// Returns a handle to the route table
+    pub fn routes(&self) -> Routes {
+        self.routes.clone()
+    }
+
+    async fn execute_callback(
+        &self,
+        peer: Option<IpAddr>,
+        identity: Option<Arc<ClientIdentity>>,
+        mut req: Request<Body>,
+    ) -> Result<Response<Body>, Infallible> {
+        let client_ip = resolve_client_ip(peer, req.headers(), &self.proxies);
+
+        if let Some(ranges) = &self.allowed_ips {
+            if let Err(e) = check_allowed(client_ip, ranges) {
+                log::warn!("{}", e);
+                return Ok(self.error_to_response(&e));
+            }
+        }
+        if let Some(client_ip) = client_ip {
+            req.extensions_mut().insert(ClientIp(client_ip));
+        }
+        if let Some(identity) = identity {
+            req.extensions_mut().insert(identity);
+        }
+
+        let path = req.uri().path()[1..].to_string();
+        let response = if let Some((cb, params)) = self.routes.find(&path) {
+            req.extensions_mut().insert(params);
             match cb.as_ref().execute(req).await {
                 Ok(res) => res,
-                Err(e) => Response::builder()
-                    .status(500)
-                    .body(Body::from(format!("{:?}", e)))
-                    .unwrap(),
+                Err(e) => {
+                    log::error!("Request to '{}' failed: {}", path, e);
+                    self.error_to_response(&e)
+                }
             }
         } else {
-            Response::builder()
-                .status(StatusCode::NOT_FOUND)
-                .body(Body::from("404 - Not Found"))
-                .unwrap()
+            self.error_response(StatusCode::NOT_FOUND, "Not Found")
         };
 
         Ok(response)
     }
 
-    pub async fn start<A: ToSocketAddrs>(self, addr: A) -> MultihookResult<()> {
-        let address: SocketAddr = addr
-            .to_socket_addrs()
-            .expect("Failed to convert address to socket address.")
-            .next()
-            .expect("No socket address specified");
+    /// Creates the response for a failed request
+    fn error_to_response(&self, error: &MultihookError) -> Response<Body> {
+        let mut response = self.error_response(error.status_code(), &error.public_message());
+
+        if let MultihookError::MethodNotAllowed(methods) = error {
+            let allow = methods
+                .iter()
+                .map(Method::as_str)
+                .collect::<Vec<_>>()
+                .join(", ");
+            if let Ok(value) = HeaderValue::from_str(&allow) {
+                response.headers_mut().insert(ALLOW, value);
+            }
+        }
+
+        response
+    }
+
+    /// Creates an error response in the configured format
+    fn error_response(&self, status: StatusCode, detail: &str) -> Response<Body> {
+        let builder = Response::builder().status(status);
+
+        match self.error_format {
+            ErrorFormat::Text => builder
+                .header(CONTENT_TYPE, "text/plain; charset=utf-8")
+                .body(Body::from(format!("{} - {}", status.as_u16(), detail))),
+            ErrorFormat::Json => {
+                let problem = serde_json::json!({
+                    "type": "about:blank",
+                    "title": status.canonical_reason().unwrap_or_default(),
+                    "status": status.as_u16(),
+                    "detail": detail,
+                });
+                builder
+                    .header(CONTENT_TYPE, "application/problem+json")
+                    .body(Body::from(problem.to_string()))
+            }
+        }
+        .unwrap()
+    }
+
+    /// Serves requests on all listeners until the shutdown signal is received
+    pub async fn start(
+        self,
+        listeners: Vec<Listener>,
+        shutdown: watch::Receiver<bool>,
+    ) -> MultihookResult<()> {
         let self_ref = Arc::new(self);
-        let service_fn = make_service_fn(|_conn| {
-            let self_ref = Arc::clone(&self_ref);
-            async move {
-                Ok::<_, Infallible>(service_fn(move |req| {
-                    let self_ref = Arc::clone(&self_ref);
-                    async move { self_ref.execute_callback(req).await }
-                }))
+        let handles = listeners
+            .into_iter()
+            .map(|listener| {
+                log::info!("Listening on {}", listener);
+                tokio::spawn(Arc::clone(&self_ref).accept_loop(listener, shutdown.clone()))
+            })
+            .collect::<Vec<_>>();
+
+        for handle in handles {
+            handle.await.expect("Listener task panicked");
+        }
+
+        Ok(())
+    }
+
+    /// Accepts connections on the listener until the shutdown signal is received
+    async fn accept_loop(self: Arc<Self>, listener: Listener, mut shutdown: watch::Receiver<bool>) {
+        loop {
+            let accepted = tokio::select! {
+                accepted = self.accept(&listener, shutdown.clone()) => accepted,
+                _ = shutdown.changed() => break,
+            };
+            if let Err(e) = accepted {
+                log::error!("Failed to accept connection on {}: {}", listener, e);
+                tokio::time::sleep(Duration::from_millis(100)).await;
             }
-        });
-        let server = Server::bind(&address).serve(service_fn);
-        server.await?;
+        }
+        log::info!("Stopped listening on {}", listener);
+    }
+
+    /// Accepts a single connection and spawns a task to handle it
+    async fn accept(
+        self: &Arc<Self>,
+        listener: &Listener,
+        shutdown: watch::Receiver<bool>,
+    ) -> io::Result<()> {
+        let self_ref = Arc::clone(self);
+
+        match listener {
+            Listener::Tcp(tcp) => {
+                let (stream, peer) = tcp.accept().await?;
+                tokio::spawn(async move {
+                    self_ref
+                        .handle_connection(stream, Some(peer.ip()), shutdown)
+                        .await
+                });
+            }
+            #[cfg(unix)]
+            Listener::Unix(unix, _) => {
+                let (stream, _) = unix.accept().await?;
+                tokio::spawn(
+                    async move { self_ref.handle_connection(stream, None, shutdown).await },
+                );
+            }
+        }
 
         Ok(())
     }
+
+    /// Serves all requests of a single connection
+    async fn handle_connection<S>(
+        self: Arc<Self>,
+        stream: S,
+        peer: Option<IpAddr>,
+        shutdown: watch::Receiver<bool>,
+    ) where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        if let Some(acceptor) = &self.tls_acceptor {
+            let stream = match acceptor.accept(stream).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log::debug!("TLS handshake with {} failed: {}", display_peer(peer), e);
+                    return;
+                }
+            };
+            let (_, session) = stream.get_ref();
+            let http2 = session.alpn_protocol() == Some(b"h2");
+            let identity = session
+                .peer_certificates()
+                .and_then(ClientIdentity::from_chain)
+                .map(Arc::new);
+            Arc::clone(&self)
+                .serve(stream, peer, identity, http2, shutdown)
+                .await
+        } else {
+            self.serve(stream, peer, None, false, shutdown).await
+        }
+    }
+
+    async fn serve<S>(
+        self: Arc<Self>,
+        stream: S,
+        peer: Option<IpAddr>,
+        identity: Option<Arc<ClientIdentity>>,
+        http2_only: bool,
+        mut shutdown: watch::Receiver<bool>,
+    ) where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let service = service_fn(move |req| {
+            let self_ref = Arc::clone(&self);
+            let identity = identity.clone();
+            async move { self_ref.execute_callback(peer, identity, req).await }
+        });
+
+        let connection = Http::new()
+            .http2_only(http2_only)
+            .serve_connection(stream, service);
+        tokio::pin!(connection);
+
+        // running requests are completed before the connection is closed on shutdown
+        let result = tokio::select! {
+            result = connection.as_mut() => result,
+            _ = shutdown.changed() => {
+                connection.as_mut().graceful_shutdown();
+                connection.await
+            }
+        };
+        if let Err(e) = result {
+            log::debug!("Connection to {} failed: {}", display_peer(peer), e);
+        }
+    }
+}
+
+fn display_peer(peer: Option<IpAddr>) -> String {
+    peer.map(|ip| ip.to_string())
+        .unwrap_or_else(|| String::from("unix socket peer"))
 }
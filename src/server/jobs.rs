@@ -0,0 +1,185 @@
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+static KILL_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+lazy_static! {
+    static ref JOBS: Jobs = Jobs::default();
+}
+
+/// Keeps track of the running hook executions and the processes they spawned
+/// so they can be drained on shutdown
+#[derive(Default)]
+pub struct Jobs {
+    state: Mutex<JobState>,
+    finished: Notify,
+}
+
+#[derive(Default)]
+struct JobState {
+    next_id: u64,
+    running: HashMap<u64, String>,
+    processes: HashMap<u32, String>,
+}
+
+/// Removes the job from the running jobs when dropped
+pub struct JobGuard(u64);
+
+/// Removes the process from the tracked processes when dropped.
+/// The process group is terminated if the process hasn't exited by then
+pub struct ProcessGuard {
+    pid: u32,
+    exited: bool,
+}
+
+/// Registers a hook execution. Requests are registered as soon as they are received
+/// so that shutdown waits for requests that are still being read or validated
+pub fn start_job<S: Into<String>>(name: S) -> JobGuard {
+    let mut state = JOBS.state.lock().unwrap();
+    let id = state.next_id;
+    state.next_id += 1;
+    state.running.insert(id, name.into());
+
+    JobGuard(id)
+}
+
+/// Registers a process spawned by an action
+pub fn track_process<S: Into<String>>(pid: u32, name: S) -> ProcessGuard {
+    JOBS.state
+        .lock()
+        .unwrap()
+        .processes
+        .insert(pid, name.into());
+
+    ProcessGuard { pid, exited: false }
+}
+
+impl ProcessGuard {
+    /// Marks the process as exited
+    pub fn exited(mut self) {
+        self.exited = true;
+    }
+}
+
+impl Drop for JobGuard {
+    fn drop(&mut self) {
+        JOBS.state.lock().unwrap().running.remove(&self.0);
+        JOBS.finished.notify_waiters();
+    }
+}
+
+impl Drop for ProcessGuard {
+    fn drop(&mut self) {
+        JOBS.state.lock().unwrap().processes.remove(&self.pid);
+
+        if !self.exited {
+            terminate_process_group(self.pid);
+        }
+    }
+}
+
+/// Waits for all running jobs to finish. Processes of jobs that are still running
+/// after the timeout are terminated and killed if they don't exit in time.
+pub async fn drain(timeout: Duration) {
+    if wait_for_jobs(timeout).await {
+        log::info!("All jobs finished");
+        return;
+    }
+    let remaining = running_jobs();
+    log::warn!(
+        "Terminating {} job(s) that didn't finish within {:?}: {}",
+        remaining.len(),
+        timeout,
+        remaining.join(", ")
+    );
+    signal_processes(Signal::Terminate);
+
+    if wait_for_jobs(KILL_GRACE_PERIOD).await {
+        log::info!("All jobs exited after being terminated");
+        return;
+    }
+    let remaining = running_jobs();
+    signal_processes(Signal::Kill);
+    log::warn!("Killed jobs: {}", remaining.join(", "));
+}
+
+/// Returns the names of the running jobs
+fn running_jobs() -> Vec<String> {
+    JOBS.state
+        .lock()
+        .unwrap()
+        .running
+        .values()
+        .cloned()
+        .collect()
+}
+
+/// Waits until no jobs are running. Returns false if the timeout elapsed before that.
+async fn wait_for_jobs(timeout: Duration) -> bool {
+    tokio::time::timeout(timeout, async {
+        loop {
+            let finished = JOBS.finished.notified();
+            if JOBS.state.lock().unwrap().running.is_empty() {
+                break;
+            }
+            finished.await;
+        }
+    })
+    .await
+    .is_ok()
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Signal {
+    Terminate,
+    Kill,
+}
+
+/// Sends the signal to the process groups of all tracked processes
+fn signal_processes(signal: Signal) {
+    for (pid, name) in JOBS.state.lock().unwrap().processes.iter() {
+        log::debug!("Sending {:?} to process {} of job {}", signal, pid, name);
+        if let Err(e) = signal_process_group(*pid, signal) {
+            log::error!("Failed to send {:?} to process {}: {}", signal, pid, e);
+        }
+    }
+}
+
+/// Terminates the process group of a process whose action was cancelled
+/// and kills it if it doesn't exit within the grace period
+fn terminate_process_group(pid: u32) {
+    log::debug!("Terminating process group {}", pid);
+    if signal_process_group(pid, Signal::Terminate).is_err() {
+        // the process group has already exited
+        return;
+    }
+    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+        handle.spawn(async move {
+            tokio::time::sleep(KILL_GRACE_PERIOD).await;
+            if signal_process_group(pid, Signal::Kill).is_ok() {
+                log::warn!("Killed process group {} after it didn't terminate", pid);
+            }
+        });
+    }
+}
+
+#[cfg(unix)]
+fn signal_process_group(pid: u32, signal: Signal) -> nix::Result<()> {
+    use nix::sys::signal::{killpg, Signal as NixSignal};
+    use nix::unistd::Pid;
+
+    let signal = match signal {
+        Signal::Terminate => NixSignal::SIGTERM,
+        Signal::Kill => NixSignal::SIGKILL,
+    };
+    killpg(Pid::from_raw(pid as i32), signal)
+}
+
+/// Processes are killed when the runtime shuts down
+#[cfg(not(unix))]
+fn signal_process_group(_pid: u32, _signal: Signal) -> Result<(), std::convert::Infallible> {
+    Ok(())
+}
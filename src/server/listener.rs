@@ -0,0 +1,220 @@
+use crate::utils::error::{MultihookError, MultihookResult};
+use crate::utils::settings::ListenerSettings;
+use crate::utils::systemd;
+use std::fmt::{self, Display, Formatter};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::PathBuf;
+use tokio::net::TcpListener;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+
+/// A bound socket the server accepts connections on
+pub enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener, PathBuf),
+}
+
+impl Display for Listener {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Listener::Tcp(listener) => match listener.local_addr() {
+                Ok(address) => write!(f, "{}", address),
+                Err(_) => write!(f, "tcp socket"),
+            },
+            #[cfg(unix)]
+            Listener::Unix(_, path) => write!(f, "unix:{}", path.to_string_lossy()),
+        }
+    }
+}
+
+/// Binds all configured listeners
+pub async fn bind_all(settings: &[ListenerSettings]) -> MultihookResult<Vec<Listener>> {
+    let mut listeners = Vec::new();
+
+    for listener in settings {
+        match listener {
+            ListenerSettings::Tcp { address } => {
+                for address in resolve_address(address)? {
+                    listeners.push(Listener::Tcp(bind_tcp(address)?));
+                }
+            }
+            ListenerSettings::Unix {
+                path,
+                mode,
+                owner,
+                group,
+            } => listeners.push(bind_unix(
+                path.clone(),
+                mode.as_deref(),
+                owner.as_deref(),
+                group.as_deref(),
+            )?),
+        }
+    }
+
+    Ok(listeners)
+}
+
+/// Takes over the listeners passed by systemd via socket activation
+#[cfg(unix)]
+pub fn from_systemd() -> MultihookResult<Vec<Listener>> {
+    use nix::sys::socket::{getsockname, AddressFamily, SockaddrLike, SockaddrStorage};
+    use std::os::unix::io::FromRawFd;
+
+    systemd::take_listen_fds()
+        .into_iter()
+        .map(|fd| {
+            let address = getsockname::<SockaddrStorage>(fd).map_err(|e| {
+                MultihookError::ListenerError(format!("Invalid socket fd {}: {}", fd, e))
+            })?;
+
+            match address.family() {
+                Some(AddressFamily::Inet) | Some(AddressFamily::Inet6) => {
+                    let listener = unsafe { std::net::TcpListener::from_raw_fd(fd) };
+                    listener.set_nonblocking(true)?;
+                    Ok(Listener::Tcp(TcpListener::from_std(listener)?))
+                }
+                Some(AddressFamily::Unix) => {
+                    let path = address
+                        .as_unix_addr()
+                        .and_then(|a| a.path())
+                        .map(PathBuf::from)
+                        .unwrap_or_default();
+                    let listener = unsafe { std::os::unix::net::UnixListener::from_raw_fd(fd) };
+                    listener.set_nonblocking(true)?;
+                    Ok(Listener::Unix(UnixListener::from_std(listener)?, path))
+                }
+                family => Err(MultihookError::ListenerError(format!(
+                    "Unsupported socket family {:?} for fd {}",
+                    family, fd
+                ))),
+            }
+        })
+        .collect()
+}
+
+#[cfg(not(unix))]
+pub fn from_systemd() -> MultihookResult<Vec<Listener>> {
+    Ok(Vec::new())
+}
+
+fn resolve_address(address: &str) -> MultihookResult<Vec<SocketAddr>> {
+    let addresses = address.to_socket_addrs()?.collect::<Vec<_>>();
+
+    if addresses.is_empty() {
+        Err(MultihookError::ListenerError(format!(
+            "Address '{}' doesn't resolve to any socket address",
+            address
+        )))
+    } else {
+        Ok(addresses)
+    }
+}
+
+/// Binds the address. Ipv6 sockets only accept ipv6 connections so that
+/// `[::]` and `0.0.0.0` can be bound at the same time
+fn bind_tcp(address: SocketAddr) -> MultihookResult<TcpListener> {
+    use socket2::{Domain, Socket, Type};
+
+    let socket = Socket::new(Domain::for_address(address), Type::STREAM, None)?;
+    if address.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
+    socket.bind(&address.into())?;
+    socket.listen(1024)?;
+    socket.set_nonblocking(true)?;
+
+    Ok(TcpListener::from_std(socket.into())?)
+}
+
+#[cfg(unix)]
+fn bind_unix(
+    path: PathBuf,
+    mode: Option<&str>,
+    owner: Option<&str>,
+    group: Option<&str>,
+) -> MultihookResult<Listener> {
+    use nix::sys::stat::{umask, Mode};
+    use nix::unistd::{chown, Group, User};
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    let mode = mode
+        .map(|mode| {
+            u32::from_str_radix(mode.trim_start_matches("0o"), 8).map_err(|_| {
+                MultihookError::ListenerError(format!("Invalid socket mode '{}'", mode))
+            })
+        })
+        .transpose()?;
+    // a socket file left behind by a previous run would prevent binding.
+    // Other files and sockets of running servers are never removed
+    if let Ok(metadata) = std::fs::symlink_metadata(&path) {
+        if !metadata.file_type().is_socket() {
+            return Err(MultihookError::ListenerError(format!(
+                "{:?} exists and is not a socket",
+                path
+            )));
+        }
+        if std::os::unix::net::UnixStream::connect(&path).is_ok() {
+            return Err(MultihookError::ListenerError(format!(
+                "{:?} is used by another process",
+                path
+            )));
+        }
+        std::fs::remove_file(&path)?;
+    }
+    // the socket is only accessible by the owner until the permissions are applied
+    let previous_umask = umask(Mode::from_bits_truncate(0o177));
+    let listener = UnixListener::bind(&path);
+    umask(previous_umask);
+    let listener = listener?;
+
+    let mode = mode.unwrap_or(0o666 & !previous_umask.bits() as u32);
+    if owner.is_some() || group.is_some() {
+        let uid = owner
+            .map(|owner| match owner.parse() {
+                Ok(uid) => Ok(nix::unistd::Uid::from_raw(uid)),
+                Err(_) => User::from_name(owner)
+                    .ok()
+                    .flatten()
+                    .map(|user| user.uid)
+                    .ok_or_else(|| {
+                        MultihookError::ListenerError(format!("Unknown user '{}'", owner))
+                    }),
+            })
+            .transpose()?;
+        let gid = group
+            .map(|group| match group.parse() {
+                Ok(gid) => Ok(nix::unistd::Gid::from_raw(gid)),
+                Err(_) => Group::from_name(group)
+                    .ok()
+                    .flatten()
+                    .map(|group| group.gid)
+                    .ok_or_else(|| {
+                        MultihookError::ListenerError(format!("Unknown group '{}'", group))
+                    }),
+            })
+            .transpose()?;
+        chown(&path, uid, gid).map_err(|e| {
+            MultihookError::ListenerError(format!("Failed to change owner of {:?}: {}", path, e))
+        })?;
+    }
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode))?;
+
+    Ok(Listener::Unix(listener, path))
+}
+
+#[cfg(not(unix))]
+fn bind_unix(
+    path: PathBuf,
+    _mode: Option<&str>,
+    _owner: Option<&str>,
+    _group: Option<&str>,
+) -> MultihookResult<Listener> {
+    Err(MultihookError::ListenerError(format!(
+        "Unix sockets are not supported on this platform ({:?})",
+        path
+    )))
+}
@@ -1,53 +1,141 @@
 use std::sync::Arc;
+use std::time::Duration;
 
-use hyper::{Body, Method, Response};
+use hyper::{Body, Response};
+use tokio::sync::watch;
 
 use endpoint::HookEndpoint;
 
+use crate::server::client_ip::ProxySettings;
 use crate::server::http::{HTTPCallback, HTTPServer};
+use crate::server::reload::EndpointRegistry;
 use crate::utils::error::MultihookResult;
+use crate::utils::settings::{ConfigLocation, Settings, SettingsOverrides};
+use crate::utils::systemd;
 
 pub mod action;
+mod client_ip;
 pub mod endpoint;
 mod http;
+pub mod jobs;
+mod listener;
+mod payload;
+mod reload;
+pub mod route;
+pub mod tls;
 
 pub struct HookServer {
     server: HTTPServer,
+    registry: EndpointRegistry,
 }
 
 impl HookServer {
-    pub fn new() -> Self {
-        Self {
-            server: HTTPServer::default(),
+    pub fn new(
+        settings: &Settings,
+        location: ConfigLocation,
+        overrides: SettingsOverrides,
+    ) -> MultihookResult<Self> {
+        let tls_acceptor = settings
+            .server
+            .tls
+            .as_ref()
+            .map(tls::create_acceptor)
+            .transpose()?;
+        let server = HTTPServer::default()
+            .error_format(settings.server.error_format)
+            .allow_ips(settings.server.allowed_ips.clone())
+            .proxies(ProxySettings {
+                trusted_proxies: settings.server.trusted_proxies.clone(),
+                header: settings.server.forwarded_header,
+                trust_unix_sockets: settings.server.trust_unix_sockets,
+            })
+            .tls(tls_acceptor);
+        let mut registry = EndpointRegistry::new(server.routes(), location, overrides);
+        registry.apply(settings.clone());
+
+        Ok(Self { server, registry })
+    }
+
+    /// Runs the server until a shutdown signal is received and
+    /// the running jobs have been drained
+    pub async fn start(self, settings: &Settings) -> MultihookResult<()> {
+        let settings = &settings.server;
+        log::info!("Starting server");
+        let mut bound = listener::from_systemd()?;
+        if bound.is_empty() {
+            bound = listener::bind_all(&settings.listeners()).await?;
+        } else {
+            log::info!("Using {} socket(s) passed by systemd", bound.len());
         }
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        tokio::spawn(async move {
+            shutdown_signal().await;
+            let _ = shutdown_tx.send(true);
+        });
+        reload::spawn_reload_task(self.registry, settings.watch_config);
+        systemd::notify("READY=1");
+        systemd::spawn_watchdog();
+
+        self.server.start(bound, shutdown_rx).await?;
+
+        let timeout = Duration::from_secs(settings.shutdown_timeout.unwrap_or(30));
+        log::info!("Waiting up to {:?} for running jobs to finish", timeout);
+        jobs::drain(timeout).await;
+
+        Ok(())
     }
+}
 
-    pub fn add_hook(&mut self, point: String, action: HookEndpoint) {
-        let action = Arc::new(action);
+/// Creates the http callback that executes the endpoint
+fn create_callback(point: String, action: HookEndpoint) -> HTTPCallback<Body, Body> {
+    let allowed_ips = action.allowed_ips().map(<[_]>::to_vec);
+    let methods = action.methods().to_vec();
+    let action = Arc::new(action);
 
-        let cb = HTTPCallback::new({
+    let mut cb = HTTPCallback::new({
+        let point = point.clone();
+        move |req| {
+            let action = Arc::clone(&action);
             let point = point.clone();
-            move |req| {
-                let action = Arc::clone(&action);
-                let point = point.clone();
-                Box::pin(async move {
-                    log::debug!("Executing hook {}", point);
-                    action.execute(req).await?;
-                    log::debug!("Hook {} executed", point);
-
-                    Ok(Response::new(Body::from(format!(
-                        "Hook '{}' executed.",
-                        point
-                    ))))
-                })
-            }
-        })
-        .allow_method(Method::POST);
-        self.server.add_callback(point, cb);
+            Box::pin(async move {
+                log::debug!("Executing hook {}", point);
+                action.execute(req).await?;
+                log::debug!("Hook {} executed", point);
+
+                Ok(Response::new(Body::from(format!(
+                    "Hook '{}' executed.",
+                    point
+                ))))
+            })
+        }
+    });
+    for method in methods {
+        cb = cb.allow_method(method);
+    }
+
+    if let Some(ranges) = allowed_ips {
+        cb.allow_ips(ranges)
+    } else {
+        cb
     }
+}
 
-    pub async fn start(self, address: &str) -> MultihookResult<()> {
-        log::info!("Starting server on {}", address);
-        self.server.start(address).await
+/// Waits for SIGTERM or SIGINT
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+
+        tokio::select! {
+            _ = terminate.recv() => log::info!("Received SIGTERM"),
+            _ = tokio::signal::ctrl_c() => log::info!("Received SIGINT"),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        log::info!("Received Ctrl-C");
     }
+    systemd::notify("STOPPING=1");
 }
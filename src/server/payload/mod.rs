@@ -0,0 +1,195 @@
+use base64::Engine;
+use hyper::Body;
+use serde_json::{Map, Value};
+
+use crate::utils::settings::BodyFormat;
+
+mod xml;
+
+/// Form fields with this name that contain json are decoded
+static PAYLOAD_FIELD: &str = "payload";
+
+/// Converts the request body to json according to the body format.
+/// With [BodyFormat::Any] form, multipart, xml and yaml bodies are converted according
+/// to their content type, other bodies have to be json. Empty bodies are converted to null
+/// and rejected for the json and form formats
+pub async fn parse_body(
+    format: BodyFormat,
+    content_type: Option<&str>,
+    body: &[u8],
+) -> Result<Value, String> {
+    let mime = content_type.map(mime_type);
+
+    match (format, mime.as_deref()) {
+        (BodyFormat::Raw, _) => Ok(Value::String(String::from_utf8_lossy(body).into_owned())),
+        (BodyFormat::Json | BodyFormat::Form, _) if body.is_empty() => {
+            Err(String::from("the body is empty"))
+        }
+        (BodyFormat::Any, _) if body.is_empty() => Ok(Value::Null),
+        (BodyFormat::Json, _) => parse_json(body),
+        (BodyFormat::Form | BodyFormat::Any, Some("application/x-www-form-urlencoded")) => {
+            Ok(decode_payload(form_to_json(body, None)))
+        }
+        (BodyFormat::Form | BodyFormat::Any, Some("multipart/form-data")) => {
+            parse_multipart(content_type.unwrap_or_default(), body)
+                .await
+                .map(decode_payload)
+        }
+        (BodyFormat::Form, _) => Err(String::from("expected form data")),
+        (BodyFormat::Any, Some("application/xml" | "text/xml")) => xml::xml_to_json(body),
+        (BodyFormat::Any, Some(m)) if m.ends_with("+xml") => xml::xml_to_json(body),
+        (
+            BodyFormat::Any,
+            Some("application/yaml" | "application/x-yaml" | "text/yaml" | "text/x-yaml"),
+        ) => serde_yaml::from_slice(body).map_err(|e| e.to_string()),
+        (BodyFormat::Any, _) => parse_json(body),
+    }
+}
+
+/// Returns the lowercase mime type of a content type without its parameters
+pub fn mime_type(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase()
+}
+
+fn parse_json(body: &[u8]) -> Result<Value, String> {
+    serde_json::from_slice(body).map_err(|e| e.to_string())
+}
+
+/// Converts url encoded form data or query parameters to a json object.
+/// Fields that occur multiple times are converted to arrays
+pub fn form_to_json(form: &[u8], skip_field: Option<&str>) -> Value {
+    let mut object = Map::new();
+
+    for (key, value) in form_urlencoded::parse(form) {
+        if Some(key.as_ref()) != skip_field {
+            insert_field(
+                &mut object,
+                key.into_owned(),
+                Value::String(value.into_owned()),
+            );
+        }
+    }
+
+    Value::Object(object)
+}
+
+/// Converts multipart form data to a json object.
+/// Files are converted to objects with their name, content type and content
+async fn parse_multipart(content_type: &str, body: &[u8]) -> Result<Value, String> {
+    let boundary = multer::parse_boundary(content_type).map_err(|e| e.to_string())?;
+    let mut multipart = multer::Multipart::new(Body::from(body.to_vec()), boundary);
+    let mut object = Map::new();
+
+    while let Some(field) = multipart.next_field().await.map_err(|e| e.to_string())? {
+        let name = field.name().unwrap_or_default().to_owned();
+        let file_name = field.file_name().map(String::from);
+        let field_type = field.content_type().map(|m| m.to_string());
+        let content = field.bytes().await.map_err(|e| e.to_string())?;
+
+        let value = if let Some(file_name) = file_name {
+            let mut file = Map::new();
+            file.insert(String::from("filename"), Value::String(file_name));
+            if let Some(field_type) = field_type {
+                file.insert(String::from("content_type"), Value::String(field_type));
+            }
+            match String::from_utf8(content.to_vec()) {
+                Ok(text) => {
+                    file.insert(String::from("content"), Value::String(text));
+                }
+                Err(_) => {
+                    let encoded = base64::engine::general_purpose::STANDARD.encode(&content);
+                    file.insert(String::from("content"), Value::String(encoded));
+                    file.insert(
+                        String::from("encoding"),
+                        Value::String(String::from("base64")),
+                    );
+                }
+            }
+            Value::Object(file)
+        } else {
+            Value::String(String::from_utf8_lossy(&content).into_owned())
+        };
+        insert_field(&mut object, name, value);
+    }
+
+    Ok(Value::Object(object))
+}
+
+/// Decodes the json in the `payload` field. The fields of the decoded object are also
+/// added to the top level so that templates written for json bodies keep working.
+/// Other form fields take precedence
+fn decode_payload(mut value: Value) -> Value {
+    let decoded = value
+        .get(PAYLOAD_FIELD)
+        .and_then(Value::as_str)
+        .and_then(|payload| serde_json::from_str::<Value>(payload).ok());
+
+    if let (Some(decoded), Value::Object(object)) = (decoded, &mut value) {
+        if let Value::Object(fields) = &decoded {
+            for (key, field) in fields {
+                if !object.contains_key(key) {
+                    object.insert(key.clone(), field.clone());
+                }
+            }
+        }
+        object.insert(String::from(PAYLOAD_FIELD), decoded);
+    }
+
+    value
+}
+
+fn insert_field(object: &mut Map<String, Value>, key: String, value: Value) {
+    match object.get_mut(&key) {
+        Some(Value::Array(values)) => values.push(value),
+        Some(existing) => *existing = Value::Array(vec![existing.take(), value]),
+        None => {
+            object.insert(key, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn it_rejects_invalid_json_without_a_known_content_type() {
+        let body = br#"{"repo": "x""#;
+
+        for content_type in [None, Some("text/plain"), Some("application/json")] {
+            assert!(parse_body(BodyFormat::Any, content_type, body)
+                .await
+                .is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn it_parses_json_without_a_known_content_type() {
+        let body = br#"{"repo": "x"}"#;
+
+        for content_type in [
+            None,
+            Some("text/plain"),
+            Some("application/vnd.github+json"),
+        ] {
+            let value = parse_body(BodyFormat::Any, content_type, body).await;
+            assert_eq!(value, Ok(json!({"repo": "x"})));
+        }
+    }
+
+    #[tokio::test]
+    async fn it_converts_empty_bodies_to_null() {
+        assert_eq!(
+            parse_body(BodyFormat::Any, Some("text/plain"), b"").await,
+            Ok(Value::Null)
+        );
+        assert!(parse_body(BodyFormat::Json, None, b"").await.is_err());
+        assert!(parse_body(BodyFormat::Form, None, b"").await.is_err());
+    }
+}
@@ -0,0 +1,93 @@
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use serde_json::{Map, Value};
+
+/// Converts an xml document to json.
+/// The root element becomes the only key of the object. Elements that only contain text
+/// become strings, other elements become objects with their attributes prefixed with `_`,
+/// their child elements and their text in `_text`. Repeated elements become arrays.
+pub fn xml_to_json(xml: &[u8]) -> Result<Value, String> {
+    let mut reader = Reader::from_reader(xml);
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    // the elements that are currently open with their name, fields and text
+    let mut stack: Vec<(String, Map<String, Value>, String)> = Vec::new();
+    let mut root = Map::new();
+
+    loop {
+        match reader
+            .read_event_into(&mut buf)
+            .map_err(|e| e.to_string())?
+        {
+            Event::Start(start) => stack.push(open_element(&start)?),
+            Event::Empty(start) => {
+                let (name, fields, text) = open_element(&start)?;
+                let parent = stack.last_mut().map(|(_, fields, _)| fields);
+                insert_element(parent.unwrap_or(&mut root), name, fields, text);
+            }
+            Event::Text(text) => {
+                if let Some((_, _, content)) = stack.last_mut() {
+                    content.push_str(&text.unescape().map_err(|e| e.to_string())?);
+                }
+            }
+            Event::CData(data) => {
+                if let Some((_, _, content)) = stack.last_mut() {
+                    content.push_str(&String::from_utf8_lossy(&data));
+                }
+            }
+            Event::End(_) => {
+                let (name, fields, text) = stack
+                    .pop()
+                    .ok_or_else(|| String::from("unexpected closing tag"))?;
+                let parent = stack.last_mut().map(|(_, fields, _)| fields);
+                insert_element(parent.unwrap_or(&mut root), name, fields, text);
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    if root.is_empty() {
+        return Err(String::from("missing root element"));
+    }
+
+    Ok(Value::Object(root))
+}
+
+fn open_element(start: &BytesStart) -> Result<(String, Map<String, Value>, String), String> {
+    let name = String::from_utf8_lossy(start.name().as_ref()).into_owned();
+    let mut fields = Map::new();
+
+    for attribute in start.attributes() {
+        let attribute = attribute.map_err(|e| e.to_string())?;
+        let key = format!("_{}", String::from_utf8_lossy(attribute.key.as_ref()));
+        let value = attribute.unescape_value().map_err(|e| e.to_string())?;
+        fields.insert(key, Value::String(value.into_owned()));
+    }
+
+    Ok((name, fields, String::new()))
+}
+
+fn insert_element(
+    parent: &mut Map<String, Value>,
+    name: String,
+    mut fields: Map<String, Value>,
+    text: String,
+) {
+    let value = if fields.is_empty() {
+        Value::String(text)
+    } else {
+        if !text.is_empty() {
+            fields.insert(String::from("_text"), Value::String(text));
+        }
+        Value::Object(fields)
+    };
+
+    match parent.get_mut(&name) {
+        Some(Value::Array(values)) => values.push(value),
+        Some(existing) => *existing = Value::Array(vec![existing.take(), value]),
+        None => {
+            parent.insert(name, value);
+        }
+    }
+}
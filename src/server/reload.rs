@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use hyper::Body;
+
+use crate::server::create_callback;
+use crate::server::endpoint::HookEndpoint;
+use crate::server::http::{HTTPCallback, RouteTable, Routes};
+use crate::server::route::RoutePattern;
+use crate::utils::settings::{ConfigLocation, Settings, SettingsOverrides};
+use crate::utils::validation::validate_config;
+
+static WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Keeps track of the registered endpoints so that endpoints whose settings
+/// didn't change keep their state when the config is reloaded
+pub struct EndpointRegistry {
+    routes: Routes,
+    location: ConfigLocation,
+    overrides: SettingsOverrides,
+    settings: Option<Settings>,
+    endpoints: HashMap<String, Arc<HTTPCallback<Body, Body>>>,
+}
+
+impl EndpointRegistry {
+    pub fn new(routes: Routes, location: ConfigLocation, overrides: SettingsOverrides) -> Self {
+        Self {
+            routes,
+            location,
+            overrides,
+            settings: None,
+            endpoints: HashMap::new(),
+        }
+    }
+
+    /// Builds the route table from the settings and replaces the current one
+    pub fn apply(&mut self, settings: Settings) {
+        let previous = self.settings.take();
+        if let Some(previous) = &previous {
+            if previous.server != settings.server {
+                log::warn!("Changes to the server settings only apply after a restart");
+            }
+        }
+        let mut endpoints = HashMap::new();
+        let mut routes = RouteTable::default();
+
+        for (name, endpoint) in &settings.endpoints {
+            let unchanged = previous
+                .as_ref()
+                .map(|previous| {
+                    previous.hooks == settings.hooks
+                        && previous.server.max_body_size == settings.server.max_body_size
+                        && previous.endpoints.get(name) == Some(endpoint)
+                })
+                .unwrap_or(false);
+            let callback = match self.endpoints.get(name) {
+                Some(callback) if unchanged => Arc::clone(callback),
+                existing => {
+                    let hook_endpoint = match HookEndpoint::from_config(name, &settings, endpoint) {
+                        Ok(hook_endpoint) => hook_endpoint,
+                        Err(e) => {
+                            log::error!("Invalid endpoint '{}': {}", name, e);
+                            continue;
+                        }
+                    };
+                    if existing.is_some() {
+                        log::info!(
+                            "Updating endpoint '{}' with path '{}'",
+                            name,
+                            &endpoint.path
+                        );
+                    } else {
+                        log::info!("Adding endpoint '{}' with path '{}'", name, &endpoint.path);
+                    }
+                    Arc::new(create_callback(endpoint.path.clone(), hook_endpoint))
+                }
+            };
+            match RoutePattern::parse(&endpoint.path, &endpoint.params) {
+                Ok(pattern) => routes.insert(endpoint.path.clone(), pattern, Arc::clone(&callback)),
+                Err(e) => log::error!("Invalid path of endpoint '{}': {}", name, e),
+            }
+            endpoints.insert(name.clone(), callback);
+        }
+        for name in self.endpoints.keys() {
+            if !endpoints.contains_key(name) {
+                log::info!("Removing endpoint '{}'", name);
+            }
+        }
+
+        self.routes.replace(routes);
+        self.endpoints = endpoints;
+        self.settings = Some(settings);
+    }
+
+    /// Loads the config from disk and applies it.
+    /// The current config stays active if the new one is invalid.
+    pub fn reload(&mut self) {
+        log::info!("Reloading config");
+        match validate_config(&self.location) {
+            Ok(mut settings) => {
+                self.overrides.apply(&mut settings);
+                self.apply(settings)
+            }
+            Err(errors) => {
+                for error in errors {
+                    log::error!("{}", error);
+                }
+                log::error!("Failed to reload config. Keeping the current one");
+            }
+        }
+    }
+}
+
+/// Reloads the config on SIGHUP and optionally when the config files change
+pub fn spawn_reload_task(mut registry: EndpointRegistry, watch_config: bool) {
+    tokio::spawn(async move {
+        #[cfg(unix)]
+        let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+            .expect("Failed to listen for SIGHUP");
+        let mut interval = tokio::time::interval(WATCH_INTERVAL);
+        let mut fingerprint = config_fingerprint(&registry.location);
+
+        loop {
+            #[cfg(unix)]
+            let hangup_received = hangup.recv();
+            #[cfg(not(unix))]
+            let hangup_received = std::future::pending::<Option<()>>();
+
+            tokio::select! {
+                _ = hangup_received => {
+                    log::info!("Received SIGHUP");
+                    fingerprint = config_fingerprint(&registry.location);
+                    registry.reload();
+                }
+                _ = interval.tick(), if watch_config => {
+                    let current = config_fingerprint(&registry.location);
+                    if current != fingerprint {
+                        log::info!("Config files changed");
+                        fingerprint = current;
+                        registry.reload();
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Returns the config files with their modification times to detect changes
+fn config_fingerprint(location: &ConfigLocation) -> Vec<(PathBuf, Option<SystemTime>)> {
+    location
+        .files()
+        .into_iter()
+        .map(|path| {
+            let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+            (path, modified)
+        })
+        .collect()
+}
@@ -0,0 +1,370 @@
+use std::collections::HashMap;
+
+use regex::Regex;
+
+use crate::utils::settings::ParamSettings;
+
+/// The values of the parameters captured from the request path
+#[derive(Clone, Debug, Default)]
+pub struct PathParams(pub HashMap<String, String>);
+
+/// A route with parameters like `deploy/{project}` or a wildcard like `static/*rest`
+#[derive(Clone, Debug)]
+pub struct RoutePattern {
+    segments: Vec<Segment>,
+    constraints: HashMap<String, Constraint>,
+}
+
+#[derive(Clone, Debug)]
+enum Segment {
+    Static(String),
+    Param(String),
+    Wildcard(String),
+}
+
+#[derive(Clone, Debug)]
+enum Constraint {
+    Pattern(Regex),
+    Values(Vec<String>),
+}
+
+impl RoutePattern {
+    /// Parses the route and the constraints of its parameters
+    pub fn parse(route: &str, params: &HashMap<String, ParamSettings>) -> Result<Self, String> {
+        let parts = route.split('/').collect::<Vec<_>>();
+        let mut segments = Vec::with_capacity(parts.len());
+
+        for (index, part) in parts.iter().enumerate() {
+            let segment = if let Some(name) = part.strip_prefix('*') {
+                if index != parts.len() - 1 {
+                    return Err(format!(
+                        "wildcard '{}' must be the last segment of the path",
+                        part
+                    ));
+                }
+                Segment::Wildcard(name.to_owned())
+            } else if let Some(name) = part.strip_prefix('{').and_then(|p| p.strip_suffix('}')) {
+                Segment::Param(name.to_owned())
+            } else if part.contains(['{', '}']) {
+                return Err(format!(
+                    "invalid segment '{}'. Parameters need to span a whole segment",
+                    part
+                ));
+            } else {
+                Segment::Static(part.to_string())
+            };
+            if let Segment::Param(name) | Segment::Wildcard(name) = &segment {
+                if !is_valid_name(name) {
+                    return Err(format!("invalid parameter name '{}'", name));
+                }
+            }
+            segments.push(segment);
+        }
+        let mut pattern = Self {
+            segments,
+            constraints: HashMap::new(),
+        };
+        let names = pattern.param_names();
+        let mut constraints = HashMap::new();
+
+        for (name, settings) in params {
+            if !names.contains(&name.as_str()) {
+                return Err(format!("parameter '{}' is not part of the path", name));
+            }
+            let constraint = match settings {
+                ParamSettings::Pattern { pattern } => Constraint::Pattern(
+                    Regex::new(&format!("^(?:{})$", pattern))
+                        .map_err(|e| format!("invalid pattern for parameter '{}': {}", name, e))?,
+                ),
+                ParamSettings::Values { values } => Constraint::Values(values.clone()),
+            };
+            constraints.insert(name.clone(), constraint);
+        }
+        pattern.constraints = constraints;
+
+        Ok(pattern)
+    }
+
+    /// Returns true if the route doesn't contain any parameters
+    pub fn is_static(&self) -> bool {
+        self.segments
+            .iter()
+            .all(|segment| matches!(segment, Segment::Static(_)))
+    }
+
+    /// Returns the names of the parameters in the route
+    pub fn param_names(&self) -> Vec<&str> {
+        self.segments
+            .iter()
+            .filter_map(|segment| match segment {
+                Segment::Static(_) => None,
+                Segment::Param(name) | Segment::Wildcard(name) => Some(name.as_str()),
+            })
+            .collect()
+    }
+
+    /// Returns the route with the parameter names left out.
+    /// Routes with the same shape match the same paths
+    pub fn shape(&self) -> String {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Static(part) => part.as_str(),
+                Segment::Param(_) => "{}",
+                Segment::Wildcard(_) => "*",
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Matches the path against the route and returns the captured parameters.
+    /// Parameters containing `.` or `..` segments never match
+    pub fn matches(&self, path: &str) -> Option<PathParams> {
+        let mut params = HashMap::new();
+        let mut parts = path.split('/');
+
+        for segment in &self.segments {
+            match segment {
+                Segment::Static(expected) => {
+                    if parts.next()? != expected {
+                        return None;
+                    }
+                }
+                Segment::Param(name) => {
+                    let value = parts.next().filter(|p| !p.is_empty())?;
+                    params.insert(name.clone(), value.to_owned());
+                }
+                Segment::Wildcard(name) => {
+                    let value = parts.by_ref().collect::<Vec<_>>().join("/");
+                    params.insert(name.clone(), value);
+                }
+            }
+        }
+        if parts.next().is_some() {
+            return None;
+        }
+        let allowed = params.iter().all(|(name, value)| {
+            let traversal = value.split('/').any(|part| part == "." || part == "..");
+            !traversal && self.is_allowed(name, value)
+        });
+
+        allowed.then_some(PathParams(params))
+    }
+
+    /// Parameters without a constraint only allow characters that are safe to use
+    /// in shell commands, wildcards additionally allow `/`
+    fn is_allowed(&self, name: &str, value: &str) -> bool {
+        match self.constraints.get(name) {
+            Some(Constraint::Pattern(regex)) => regex.is_match(value),
+            Some(Constraint::Values(values)) => values.iter().any(|v| v == value),
+            None => {
+                value.is_empty()
+                    || value
+                        .split('/')
+                        .all(|part| !part.is_empty() && part.chars().all(is_safe_char))
+            }
+        }
+    }
+
+    /// Routes with more static segments take precedence.
+    /// Routes ending in a wildcard are matched last
+    fn precedence(&self) -> (bool, usize) {
+        let wildcard = matches!(self.segments.last(), Some(Segment::Wildcard(_)));
+        let static_segments = self
+            .segments
+            .iter()
+            .filter(|segment| matches!(segment, Segment::Static(_)))
+            .count();
+
+        (!wildcard, static_segments)
+    }
+}
+
+fn is_safe_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-')
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Routes with parameters sorted by their precedence
+pub struct PatternRoutes<T>(Vec<(String, RoutePattern, T)>);
+
+impl<T> Default for PatternRoutes<T> {
+    fn default() -> Self {
+        Self(Vec::new())
+    }
+}
+
+impl<T> PatternRoutes<T> {
+    pub fn insert(&mut self, route: String, pattern: RoutePattern, value: T) {
+        self.0.push((route, pattern, value));
+        self.0.sort_by(|(route_a, a, _), (route_b, b, _)| {
+            b.precedence()
+                .cmp(&a.precedence())
+                .then_with(|| route_a.cmp(route_b))
+        });
+    }
+
+    /// Returns the first route matching the path with the captured parameters
+    pub fn find(&self, path: &str) -> Option<(&T, PathParams)> {
+        self.0
+            .iter()
+            .find_map(|(_, pattern, value)| pattern.matches(path).map(|params| (value, params)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(route: &str) -> RoutePattern {
+        RoutePattern::parse(route, &HashMap::new()).unwrap()
+    }
+
+    fn captured(pattern: &RoutePattern, path: &str) -> Option<Vec<(String, String)>> {
+        let mut params = pattern.matches(path)?.0.into_iter().collect::<Vec<_>>();
+        params.sort();
+        Some(params)
+    }
+
+    fn param(name: &str, value: &str) -> (String, String) {
+        (name.to_owned(), value.to_owned())
+    }
+
+    #[test]
+    fn it_captures_parameters() {
+        let route = pattern("deploy/{project}/{env}");
+
+        assert_eq!(
+            captured(&route, "deploy/website/staging"),
+            Some(vec![param("env", "staging"), param("project", "website")])
+        );
+        assert_eq!(captured(&route, "deploy/website"), None);
+        assert_eq!(captured(&route, "deploy/website/"), None);
+        assert_eq!(captured(&route, "deploy/website/staging/1"), None);
+        assert_eq!(captured(&route, "build/website/staging"), None);
+    }
+
+    #[test]
+    fn it_only_allows_safe_characters_without_constraints() {
+        let route = pattern("deploy/{project}");
+
+        assert!(route.matches("deploy/my-site_v1.2").is_some());
+        for value in [
+            "$(id)", "a;b", "a%20b", "a b", "`id`", "a|b", "a&b", "'a'", "\"a\"", "a\\b", "ä",
+        ] {
+            assert!(
+                route.matches(&format!("deploy/{}", value)).is_none(),
+                "{} was allowed",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn it_rejects_traversal_segments() {
+        let route = pattern("deploy/{project}");
+        let files = pattern("static/*rest");
+
+        assert!(route.matches("deploy/.").is_none());
+        assert!(route.matches("deploy/..").is_none());
+        assert!(route.matches("deploy/..a").is_some());
+        assert!(files.matches("static/a/../b").is_none());
+        assert!(files.matches("static/./b").is_none());
+        assert!(files.matches("static/..").is_none());
+    }
+
+    #[test]
+    fn it_captures_the_rest_of_the_path_with_wildcards() {
+        let route = pattern("static/*rest");
+
+        assert_eq!(
+            captured(&route, "static/css/main.css"),
+            Some(vec![param("rest", "css/main.css")])
+        );
+        assert_eq!(captured(&route, "static"), Some(vec![param("rest", "")]));
+        assert_eq!(captured(&route, "static/a//b"), None);
+        assert_eq!(captured(&route, "static/a/$(id)"), None);
+    }
+
+    #[test]
+    fn it_applies_constraints() {
+        let params = HashMap::from([
+            (
+                String::from("project"),
+                ParamSettings::Pattern {
+                    pattern: String::from("[a-z]+|api"),
+                },
+            ),
+            (
+                String::from("env"),
+                ParamSettings::Values {
+                    values: vec![String::from("staging"), String::from("prod uction")],
+                },
+            ),
+        ]);
+        let route = RoutePattern::parse("deploy/{project}/{env}", &params).unwrap();
+
+        assert!(route.matches("deploy/website/staging").is_some());
+        assert!(route.matches("deploy/api/staging").is_some());
+        // the pattern has to match the whole value
+        assert!(route.matches("deploy/website1/staging").is_none());
+        assert!(route.matches("deploy/website/production").is_none());
+        // constraints replace the default character restriction
+        assert!(route.matches("deploy/website/prod uction").is_some());
+    }
+
+    #[test]
+    fn it_rejects_invalid_routes() {
+        let unknown = HashMap::from([(
+            String::from("other"),
+            ParamSettings::Values { values: Vec::new() },
+        )]);
+        let invalid_pattern = HashMap::from([(
+            String::from("project"),
+            ParamSettings::Pattern {
+                pattern: String::from("("),
+            },
+        )]);
+
+        assert!(RoutePattern::parse("static/*rest/more", &HashMap::new()).is_err());
+        assert!(RoutePattern::parse("deploy/v{version}", &HashMap::new()).is_err());
+        assert!(RoutePattern::parse("deploy/{pro-ject}", &HashMap::new()).is_err());
+        assert!(RoutePattern::parse("deploy/{project}", &unknown).is_err());
+        assert!(RoutePattern::parse("deploy/{project}", &invalid_pattern).is_err());
+    }
+
+    #[test]
+    fn it_compares_the_shape_of_routes() {
+        assert_eq!(pattern("deploy/{a}").shape(), pattern("deploy/{b}").shape());
+        assert_ne!(pattern("deploy/{a}").shape(), pattern("deploy/*a").shape());
+    }
+
+    #[test]
+    fn it_prefers_routes_with_more_static_segments() {
+        let mut routes = PatternRoutes::default();
+        for route in [
+            "deploy/*rest",
+            "deploy/{project}/{env}",
+            "deploy/{project}/production",
+            "deploy/website/production",
+        ] {
+            routes.insert(route.to_owned(), pattern(route), route);
+        }
+        let find = |path| routes.find(path).map(|(route, _)| *route);
+
+        assert_eq!(
+            find("deploy/website/production"),
+            Some("deploy/website/production")
+        );
+        assert_eq!(
+            find("deploy/api/production"),
+            Some("deploy/{project}/production")
+        );
+        assert_eq!(find("deploy/api/staging"), Some("deploy/{project}/{env}"));
+        assert_eq!(find("deploy/api"), Some("deploy/*rest"));
+        assert_eq!(find("build/api"), None);
+    }
+}
@@ -0,0 +1,226 @@
+use crate::utils::error::{MultihookError, MultihookResult};
+use crate::utils::settings::TlsSettings;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::BufReader;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio_rustls::rustls::server::{
+    AllowAnyAnonymousOrAuthenticatedClient, ClientHello, ResolvesServerCert,
+};
+use tokio_rustls::rustls::sign::{any_supported_type, CertifiedKey};
+use tokio_rustls::rustls::{Certificate, PrivateKey, RootCertStore, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+use x509_parser::prelude::{FromDer, GeneralName, X509Certificate};
+
+static RELOAD_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Resolves the server certificate and reloads it when
+/// the certificate or key files change on disk
+pub struct CertificateResolver {
+    certificate_path: PathBuf,
+    key_path: PathBuf,
+    current: RwLock<CurrentCertificate>,
+}
+
+struct CurrentCertificate {
+    key: Arc<CertifiedKey>,
+    modified: Option<(SystemTime, SystemTime)>,
+}
+
+impl CertificateResolver {
+    pub fn new(settings: &TlsSettings) -> MultihookResult<Self> {
+        let key = load_certified_key(&settings.certificate, &settings.key)?;
+        let modified = modification_times(&settings.certificate, &settings.key);
+
+        Ok(Self {
+            certificate_path: settings.certificate.clone(),
+            key_path: settings.key.clone(),
+            current: RwLock::new(CurrentCertificate { key, modified }),
+        })
+    }
+
+    /// Reloads the certificate if the files have been modified since the last load
+    pub fn reload_if_changed(&self) -> MultihookResult<bool> {
+        let modified = modification_times(&self.certificate_path, &self.key_path);
+        if modified.is_none() || modified == self.current.read().unwrap().modified {
+            return Ok(false);
+        }
+        let key = load_certified_key(&self.certificate_path, &self.key_path)?;
+        *self.current.write().unwrap() = CurrentCertificate { key, modified };
+
+        Ok(true)
+    }
+
+    /// Periodically checks the certificate files for changes
+    pub fn spawn_reload_task(self: &Arc<Self>) {
+        let resolver = Arc::clone(self);
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(RELOAD_INTERVAL);
+            loop {
+                interval.tick().await;
+                match resolver.reload_if_changed() {
+                    Ok(true) => log::info!(
+                        "Reloaded TLS certificate from {:?}",
+                        resolver.certificate_path
+                    ),
+                    Ok(false) => {}
+                    Err(e) => log::error!("Failed to reload TLS certificate: {}", e),
+                }
+            }
+        });
+    }
+}
+
+impl ResolvesServerCert for CertificateResolver {
+    fn resolve(&self, _client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        Some(Arc::clone(&self.current.read().unwrap().key))
+    }
+}
+
+/// The identity of a client that authenticated with a verified certificate.
+/// It is stored in the request extensions.
+#[derive(Clone, Debug)]
+pub struct ClientIdentity {
+    /// The distinguished name of the certificate subject
+    pub subject: String,
+    pub common_name: Option<String>,
+    /// DNS names, email addresses, URIs and ip addresses of the subject alternative name
+    pub alt_names: Vec<String>,
+}
+
+impl ClientIdentity {
+    /// Parses the identity from the leaf certificate of the verified chain
+    pub fn from_chain(chain: &[Certificate]) -> Option<Self> {
+        let (_, certificate) = X509Certificate::from_der(&chain.first()?.0).ok()?;
+        let subject = certificate.subject();
+        let common_name = subject
+            .iter_common_name()
+            .next()
+            .and_then(|cn| cn.as_str().ok())
+            .map(String::from);
+        let alt_names = certificate
+            .subject_alternative_name()
+            .ok()
+            .flatten()
+            .map(|san| {
+                san.value
+                    .general_names
+                    .iter()
+                    .filter_map(|name| match name {
+                        GeneralName::DNSName(n)
+                        | GeneralName::RFC822Name(n)
+                        | GeneralName::URI(n) => Some(n.to_string()),
+                        GeneralName::IPAddress(bytes) => {
+                            ip_from_bytes(bytes).map(|ip| ip.to_string())
+                        }
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Some(Self {
+            subject: subject.to_string(),
+            common_name,
+            alt_names,
+        })
+    }
+
+    /// The name that identifies the client. This is the common name
+    /// or the first alternative name if the subject doesn't have a common name.
+    pub fn name(&self) -> &str {
+        self.common_name
+            .as_deref()
+            .or_else(|| self.alt_names.first().map(String::as_str))
+            .unwrap_or(&self.subject)
+    }
+}
+
+/// Creates a tls acceptor that serves http/1.1 and h2
+pub fn create_acceptor(settings: &TlsSettings) -> MultihookResult<TlsAcceptor> {
+    let resolver = Arc::new(CertificateResolver::new(settings)?);
+    resolver.spawn_reload_task();
+
+    let builder = ServerConfig::builder().with_safe_defaults();
+    let builder = if let Some(client_ca) = &settings.client_ca {
+        let roots = load_root_store(client_ca)?;
+        builder
+            .with_client_cert_verifier(AllowAnyAnonymousOrAuthenticatedClient::new(roots).boxed())
+    } else {
+        builder.with_no_client_auth()
+    };
+    let mut config = builder.with_cert_resolver(resolver);
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Loads the certificate authorities that client certificates are verified against
+fn load_root_store(path: &Path) -> MultihookResult<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    let certificates = rustls_pemfile::certs(&mut BufReader::new(File::open(path)?))?;
+    let (added, _) = roots.add_parsable_certificates(&certificates);
+
+    if added == 0 {
+        return Err(MultihookError::TlsError(format!(
+            "No CA certificates found in {:?}",
+            path
+        )));
+    }
+
+    Ok(roots)
+}
+
+fn ip_from_bytes(bytes: &[u8]) -> Option<IpAddr> {
+    match bytes.len() {
+        4 => Some(IpAddr::from(<[u8; 4]>::try_from(bytes).ok()?)),
+        16 => Some(IpAddr::from(<[u8; 16]>::try_from(bytes).ok()?)),
+        _ => None,
+    }
+}
+
+fn load_certified_key(
+    certificate_path: &Path,
+    key_path: &Path,
+) -> MultihookResult<Arc<CertifiedKey>> {
+    let certificates = rustls_pemfile::certs(&mut BufReader::new(File::open(certificate_path)?))?
+        .into_iter()
+        .map(Certificate)
+        .collect::<Vec<_>>();
+    if certificates.is_empty() {
+        return Err(MultihookError::TlsError(format!(
+            "No certificates found in {:?}",
+            certificate_path
+        )));
+    }
+    let key = rustls_pemfile::read_all(&mut BufReader::new(File::open(key_path)?))?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| {
+            MultihookError::TlsError(format!("No private key found in {:?}", key_path))
+        })?;
+    let signing_key = any_supported_type(&key).map_err(|_| {
+        MultihookError::TlsError(format!("Unsupported private key in {:?}", key_path))
+    })?;
+
+    Ok(Arc::new(CertifiedKey::new(certificates, signing_key)))
+}
+
+fn modification_times(
+    certificate_path: &Path,
+    key_path: &Path,
+) -> Option<(SystemTime, SystemTime)> {
+    let certificate = std::fs::metadata(certificate_path).ok()?.modified().ok()?;
+    let key = std::fs::metadata(key_path).ok()?.modified().ok()?;
+
+    Some((certificate, key))
+}
@@ -1,3 +1,5 @@
+use hyper::{Method, StatusCode};
+use std::net::IpAddr;
 use std::string::FromUtf8Error;
 use thiserror::Error;
 
@@ -20,19 +22,108 @@ pub enum MultihookError {
     #[error(transparent)]
     ConfigError(#[from] config::ConfigError),
 
+    #[error("Failed to interpolate environment variables: {0}")]
+    InterpolationError(String),
+
+    #[error("Failed to resolve profile: {0}")]
+    ProfileError(String),
+
     #[error(transparent)]
     Hyper(#[from] hyper::Error),
 
+    #[error("Failed to set up listener: {0}")]
+    ListenerError(String),
+
+    #[error("TLS error: {0}")]
+    TlsError(String),
+
+    #[error("Access from {0} is not allowed.")]
+    ForbiddenAddress(IpAddr),
+
+    #[error("Access from an unknown address is not allowed.")]
+    UnknownAddress,
+
+    #[error("Client certificate of '{0}' is not allowed.")]
+    UntrustedClient(String),
+
+    #[error("Method not allowed.")]
+    MethodNotAllowed(Vec<Method>),
+
+    #[error("Missing credentials.")]
+    MissingSecret,
+
     #[error("Secret validation failed.")]
     InvalidSecret,
 
     #[error(transparent)]
     JsonError(#[from] serde_json::Error),
 
+    #[error("Malformed request body: {0}")]
+    InvalidBody(String),
+
+    #[error("Invalid action template: {0}")]
+    TemplateError(String),
+
+    #[error("Placeholder '{{{{{0}}}}}' has no value")]
+    EmptyPlaceholder(String),
+
+    #[error("Placeholder '{{{{{0}}}}}' {1}")]
+    InvalidPlaceholder(String, String),
+
+    #[error("Request body exceeds the limit of {0} bytes")]
+    PayloadTooLarge(usize),
+
+    #[error("Action timed out after {0} seconds")]
+    Timeout(u64),
+
     #[error("Action failed: {0}")]
     ActionError(String),
 }
 
+impl MultihookError {
+    /// Returns the http status code that is sent to the client for this error
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            MultihookError::MissingSecret => StatusCode::UNAUTHORIZED,
+            MultihookError::InvalidSecret
+            | MultihookError::ForbiddenAddress(_)
+            | MultihookError::UnknownAddress
+            | MultihookError::UntrustedClient(_) => StatusCode::FORBIDDEN,
+            MultihookError::UTF8Error(_)
+            | MultihookError::JsonError(_)
+            | MultihookError::InvalidBody(_)
+            | MultihookError::EmptyPlaceholder(_)
+            | MultihookError::InvalidPlaceholder(_, _) => StatusCode::BAD_REQUEST,
+            MultihookError::MethodNotAllowed(_) => StatusCode::METHOD_NOT_ALLOWED,
+            MultihookError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            MultihookError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Returns a message that is safe to be sent to the client.
+    /// Details of internal errors are only written to the log.
+    pub fn public_message(&self) -> String {
+        match self {
+            MultihookError::MissingSecret
+            | MultihookError::InvalidSecret
+            | MultihookError::MethodNotAllowed(_)
+            | MultihookError::PayloadTooLarge(_)
+            | MultihookError::Timeout(_)
+            | MultihookError::EmptyPlaceholder(_)
+            | MultihookError::InvalidPlaceholder(_, _) => self.to_string(),
+            MultihookError::UTF8Error(_)
+            | MultihookError::JsonError(_)
+            | MultihookError::InvalidBody(_) => String::from("Malformed request body."),
+            MultihookError::ForbiddenAddress(_)
+            | MultihookError::UnknownAddress
+            | MultihookError::UntrustedClient(_) => String::from("Access denied."),
+            MultihookError::ActionError(_) => String::from("Action failed."),
+            _ => String::from("Internal server error."),
+        }
+    }
+}
+
 pub trait LogErr {
     fn log_err<S: AsRef<str>>(&self, template: S);
 }
@@ -0,0 +1,75 @@
+//! Interpolation of environment variables in config values.
+//! `${VAR}` is replaced with the value of the variable and `${VAR:-default}`
+//! falls back to the default if the variable is unset or empty.
+//! `$${` produces a literal `${`.
+
+use std::env;
+
+/// Replaces the variables in all string values of the config.
+/// The error contains the dotted key of the invalid value
+pub fn interpolate_value(value: &mut toml::Value) -> Result<(), String> {
+    interpolate_at(value, "")
+}
+
+fn interpolate_at(value: &mut toml::Value, key: &str) -> Result<(), String> {
+    match value {
+        toml::Value::String(s) => *s = interpolate(s).map_err(|e| format!("{}: {}", key, e))?,
+        toml::Value::Array(values) => {
+            for value in values {
+                interpolate_at(value, key)?;
+            }
+        }
+        toml::Value::Table(table) => {
+            for (name, value) in table.iter_mut() {
+                if key.is_empty() {
+                    interpolate_at(value, name)?;
+                } else {
+                    interpolate_at(value, &format!("{}.{}", key, name))?;
+                }
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Replaces the variables in the string
+pub fn interpolate(input: &str) -> Result<String, String> {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(start) = rest.find('$') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(escaped) = rest.strip_prefix("$${") {
+            output.push_str("${");
+            rest = escaped;
+        } else if let Some(variable) = rest.strip_prefix("${") {
+            let end = variable
+                .find('}')
+                .ok_or_else(|| format!("unclosed variable in '{}'", input))?;
+            output.push_str(&resolve(&variable[..end])?);
+            rest = &variable[end + 1..];
+        } else {
+            output.push('$');
+            rest = &rest[1..];
+        }
+    }
+    output.push_str(rest);
+
+    Ok(output)
+}
+
+fn resolve(expression: &str) -> Result<String, String> {
+    let (name, default) = match expression.split_once(":-") {
+        Some((name, default)) => (name, Some(default)),
+        None => (expression, None),
+    };
+    let value = env::var(name).ok().filter(|v| !v.is_empty());
+
+    value
+        .or_else(|| default.map(String::from))
+        .ok_or_else(|| format!("environment variable '{}' is not set", name))
+}
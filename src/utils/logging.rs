@@ -6,7 +6,7 @@ use log::{Level, LevelFilter};
 
 /// Initializes the env_logger with a custom format
 /// that also logs the thread names
-pub fn init_logger() {
+pub fn init_logger(level: Option<LevelFilter>) {
     fern::Dispatch::new()
         .format(|out, message, record| {
             let color = get_level_style(record.level());
@@ -26,12 +26,12 @@ pub fn init_logger() {
                 message
             ))
         })
-        .level(
+        .level(level.unwrap_or_else(|| {
             std::env::var("RUST_LOG")
                 .ok()
                 .and_then(|level| log::LevelFilter::from_str(&level).ok())
-                .unwrap_or(LevelFilter::Info),
-        )
+                .unwrap_or(LevelFilter::Info)
+        }))
         .level_for("tokio", log::LevelFilter::Info)
         .level_for("tracing", log::LevelFilter::Warn)
         .level_for("rustls", log::LevelFilter::Warn)
@@ -1,3 +1,7 @@
 pub mod error;
+pub mod interpolation;
 pub mod logging;
+pub mod profiles;
 pub mod settings;
+pub mod systemd;
+pub mod validation;
@@ -0,0 +1,77 @@
+//! Endpoint profiles. An endpoint with `extends = "name"` inherits all fields
+//! it doesn't set itself from the profile. Tables are merged field by field.
+
+use toml::{Table, Value};
+
+/// Collects the profiles of all config files. Later files override the fields of earlier ones
+pub fn collect_profiles<'a, I: IntoIterator<Item = &'a Value>>(values: I) -> Table {
+    let mut profiles = Table::new();
+
+    for profile in values
+        .into_iter()
+        .filter_map(|value| value.get("profiles").and_then(Value::as_table))
+    {
+        merge(&mut profiles, profile);
+    }
+
+    profiles
+}
+
+/// Merges the inherited profile fields into the endpoints of the config.
+/// The error contains the dotted key of the invalid value
+pub fn resolve_profiles(value: &mut Value, profiles: &Table) -> Result<(), String> {
+    let Some(endpoints) = value.get_mut("endpoints").and_then(Value::as_table_mut) else {
+        return Ok(());
+    };
+    for (name, endpoint) in endpoints.iter_mut() {
+        let Some(endpoint) = endpoint.as_table_mut() else {
+            continue;
+        };
+        let Some(extends) = endpoint.get("extends").and_then(Value::as_str) else {
+            continue;
+        };
+        let mut resolved = resolve_profile(extends, profiles, &mut Vec::new())
+            .map_err(|e| format!("endpoints.{}.extends: {}", name, e))?;
+        merge(&mut resolved, endpoint);
+        *endpoint = resolved;
+    }
+
+    Ok(())
+}
+
+/// Returns the fields of the profile including the ones it inherits itself
+fn resolve_profile(name: &str, profiles: &Table, chain: &mut Vec<String>) -> Result<Table, String> {
+    if chain.iter().any(|profile| profile == name) {
+        chain.push(name.to_owned());
+        return Err(format!(
+            "circular profile inheritance {}",
+            chain.join(" -> ")
+        ));
+    }
+    chain.push(name.to_owned());
+    let mut profile = profiles
+        .get(name)
+        .and_then(Value::as_table)
+        .ok_or_else(|| format!("unknown profile '{}'", name))?
+        .clone();
+
+    if let Some(Value::String(parent)) = profile.remove("extends") {
+        let mut resolved = resolve_profile(&parent, profiles, chain)?;
+        merge(&mut resolved, &profile);
+        Ok(resolved)
+    } else {
+        Ok(profile)
+    }
+}
+
+/// Merges the fields of `other` into `table`. Nested tables are merged recursively
+pub fn merge(table: &mut Table, other: &Table) {
+    for (key, value) in other {
+        match (table.get_mut(key), value) {
+            (Some(Value::Table(existing)), Value::Table(value)) => merge(existing, value),
+            _ => {
+                table.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
@@ -1,95 +1,403 @@
 use crate::secret_validation::SecretFormat;
-use crate::utils::error::MultihookResult;
-use config::{Config, File};
-use lazy_static::lazy_static;
+use crate::utils::error::{MultihookError, MultihookResult};
+use crate::utils::interpolation::interpolate_value;
+use crate::utils::profiles::{collect_profiles, resolve_profiles};
+use config::{Config, File, FileFormat};
+use ipnet::IpNet;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
 use std::fs;
 use std::path::{Path, PathBuf};
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Default, Clone, Debug, PartialEq)]
+#[serde(default)]
+#[schemars(deny_unknown_fields)]
 pub struct Settings {
     pub server: ServerSettings,
     pub hooks: Option<Hooks>,
+    /// Endpoint settings that can be inherited with `extends`
+    pub profiles: HashMap<String, ProfileSettings>,
     pub endpoints: HashMap<String, EndpointSettings>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Default, Clone, Debug, PartialEq)]
+#[schemars(deny_unknown_fields)]
 pub struct ServerSettings {
     pub address: Option<String>,
+    /// Addresses and sockets the server listens on. Takes precedence over `address`
+    #[serde(default)]
+    pub listeners: Vec<ListenerSettings>,
+    /// The maximum size of a request body in bytes
+    pub max_body_size: Option<usize>,
+    #[serde(default)]
+    pub error_format: ErrorFormat,
+    /// Address ranges that are allowed to call any endpoint
+    #[schemars(with = "Option<Vec<String>>")]
+    pub allowed_ips: Option<Vec<IpNet>>,
+    /// Proxies whose forwarding headers are used to determine the client address
+    #[serde(default)]
+    #[schemars(with = "Vec<String>")]
+    pub trusted_proxies: Vec<IpNet>,
+    /// The header the trusted proxies write the client address to
+    #[serde(default)]
+    pub forwarded_header: ForwardedHeader,
+    /// Use the forwarding headers of requests received over unix sockets
+    #[serde(default)]
+    pub trust_unix_sockets: bool,
+    pub tls: Option<TlsSettings>,
+    /// Reload the config automatically when the config files change
+    #[serde(default)]
+    pub watch_config: bool,
+    /// The time in seconds running jobs are given to finish on shutdown before they are killed
+    pub shutdown_timeout: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[schemars(deny_unknown_fields)]
+pub struct TlsSettings {
+    /// Path to the PEM encoded certificate chain
+    pub certificate: PathBuf,
+    /// Path to the PEM encoded private key
+    pub key: PathBuf,
+    /// Path to the PEM encoded certificate authorities that client certificates are verified against
+    pub client_ca: Option<PathBuf>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum ListenerSettings {
+    /// A tcp socket. All addresses the host name resolves to are bound
+    Tcp { address: String },
+    /// A unix domain socket
+    Unix {
+        path: PathBuf,
+        /// The permissions of the socket file in octal notation
+        mode: Option<String>,
+        /// The user that owns the socket file
+        owner: Option<String>,
+        /// The group that owns the socket file
+        group: Option<String>,
+    },
 }
 
-#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+/// Settings passed on the command line that take precedence over the config
+#[derive(Clone, Debug, Default)]
+pub struct SettingsOverrides {
+    pub listeners: Option<Vec<ListenerSettings>>,
+}
+
+impl SettingsOverrides {
+    pub fn apply(&self, settings: &mut Settings) {
+        if let Some(listeners) = &self.listeners {
+            settings.server.listeners = listeners.clone();
+        }
+    }
+}
+
+impl ServerSettings {
+    /// Returns the configured listeners falling back to the `address`
+    /// or `127.0.0.1:8080` if none are configured
+    pub fn listeners(&self) -> Vec<ListenerSettings> {
+        if self.listeners.is_empty() {
+            vec![ListenerSettings::Tcp {
+                address: self
+                    .address
+                    .clone()
+                    .unwrap_or_else(|| String::from("127.0.0.1:8080")),
+            }]
+        } else {
+            self.listeners.clone()
+        }
+    }
+}
+
+/// The format error responses are sent in
+#[derive(Serialize, Deserialize, JsonSchema, Default, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorFormat {
+    #[default]
+    Text,
+    /// RFC 7807 problem details
+    Json,
+}
+
+/// The forwarding header that is used to determine the client address
+#[derive(Serialize, Deserialize, JsonSchema, Default, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ForwardedHeader {
+    #[default]
+    XForwardedFor,
+    /// RFC 7239 `Forwarded` header
+    Forwarded,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Default, Clone, Debug, PartialEq)]
+#[schemars(deny_unknown_fields)]
 pub struct Hooks {
     pub pre_action: Option<String>,
     pub post_action: Option<String>,
     pub err_action: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[schemars(deny_unknown_fields)]
 pub struct EndpointSettings {
     pub path: String,
+    /// Required unless inherited from a profile
+    #[schemars(default)]
     pub action: String,
+    /// The profile the fields that aren't set are inherited from
+    pub extends: Option<String>,
+    /// Variables that can be used in the templates with `{{vars.name}}`
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
+    /// Constraints for the values of the path parameters
+    #[serde(default)]
+    pub params: HashMap<String, ParamSettings>,
+    /// The http methods the endpoint can be called with. Defaults to POST
+    pub methods: Option<Vec<String>>,
+    #[serde(default)]
+    pub body_format: BodyFormat,
+    /// Abort if a placeholder of the action resolves to an empty value
+    #[serde(default)]
+    pub strict_placeholders: bool,
     pub hooks: Option<Hooks>,
     #[serde(default)]
     pub allow_parallel: bool,
     #[serde(default)]
     pub run_detached: bool,
+    /// The maximum time in seconds a synchronous action is allowed to run
+    pub timeout: Option<u64>,
+    /// Address ranges that are allowed to call this endpoint
+    #[schemars(with = "Option<Vec<String>>")]
+    pub allowed_ips: Option<Vec<IpNet>>,
+    /// Restricts the endpoint to clients with a matching verified certificate
+    pub client_certificate: Option<ClientCertificateSettings>,
     pub secret: Option<SecretSettings>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+/// The format request bodies are parsed in
+#[derive(Serialize, Deserialize, JsonSchema, Default, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BodyFormat {
+    /// Json regardless of the content type
+    Json,
+    /// Url encoded or multipart form data
+    Form,
+    /// Not parsed. The body is available as string with `{{$}}`
+    Raw,
+    /// Detected by the content type
+    #[default]
+    Any,
+}
+
+/// The values a path parameter is allowed to have
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum ParamSettings {
+    /// A regular expression the whole value has to match
+    Pattern { pattern: String },
+    /// A list of allowed values
+    Values { values: Vec<String> },
+}
+
+/// Endpoint settings that are inherited by the endpoints extending the profile.
+/// Profiles are resolved before the settings are deserialized, this type describes their fields.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[schemars(deny_unknown_fields)]
+pub struct ProfileSettings {
+    /// The profile the fields that aren't set are inherited from
+    pub extends: Option<String>,
+    pub action: Option<String>,
+    pub methods: Option<Vec<String>>,
+    pub body_format: Option<BodyFormat>,
+    pub strict_placeholders: Option<bool>,
+    pub hooks: Option<Hooks>,
+    pub allow_parallel: Option<bool>,
+    pub run_detached: Option<bool>,
+    pub timeout: Option<u64>,
+    #[schemars(with = "Option<Vec<String>>")]
+    pub allowed_ips: Option<Vec<IpNet>>,
+    pub client_certificate: Option<ClientCertificateSettings>,
+    pub secret: Option<ProfileSecretSettings>,
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
+}
+
+/// Secret settings of a profile. The value is usually set by the endpoint
+#[derive(Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+#[schemars(deny_unknown_fields)]
+pub struct ProfileSecretSettings {
+    pub value: Option<String>,
+    pub format: Option<SecretFormat>,
+}
+
+impl Debug for ProfileSecretSettings {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProfileSecretSettings")
+            .field("value", &self.value.as_ref().map(|_| "<redacted>"))
+            .field("format", &self.format)
+            .finish()
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[schemars(deny_unknown_fields)]
+pub struct ClientCertificateSettings {
+    /// Allowed subject distinguished names or common names
+    #[serde(default)]
+    pub subjects: Vec<String>,
+    /// Allowed subject alternative names
+    #[serde(default)]
+    pub alt_names: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+#[schemars(deny_unknown_fields)]
 pub struct SecretSettings {
     pub value: String,
     pub format: SecretFormat,
 }
 
-impl Default for Settings {
-    fn default() -> Self {
-        Self {
-            endpoints: HashMap::new(),
-            server: ServerSettings { address: None },
-            hooks: None,
-        }
+impl Debug for SecretSettings {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecretSettings")
+            .field("value", &"<redacted>")
+            .field("format", &self.format)
+            .finish()
     }
 }
 
-pub fn get_settings() -> &'static Settings {
-    lazy_static! {
-        static ref SETTINGS: Settings = load_settings().expect("Failed to get settings");
+static LOCAL_CONFIG_FILE: &str = ".multihook.toml";
+static CONFIG_EXTENSIONS: &[&str] = &["toml", "yaml", "yml", "json"];
+
+/// The location the settings are loaded from
+#[derive(Clone, Debug)]
+pub enum ConfigLocation {
+    /// The multihook directory in the user config directory
+    /// and the `.multihook.toml` file in the working directory
+    Default,
+    /// A single config file
+    File(PathBuf),
+    /// A directory containing config files
+    Dir(PathBuf),
+}
+
+impl ConfigLocation {
+    pub fn from_path(path: Option<PathBuf>) -> Self {
+        match path {
+            Some(path) if path.is_dir() => ConfigLocation::Dir(path),
+            Some(path) => ConfigLocation::File(path),
+            None => ConfigLocation::Default,
+        }
     }
 
-    &*SETTINGS
+    /// Returns all config files that are currently present
+    pub fn files(&self) -> Vec<PathBuf> {
+        let mut files = self.base_files();
+        let local = PathBuf::from(LOCAL_CONFIG_FILE);
+        if let (ConfigLocation::Default, true) = (self, local.exists()) {
+            files.push(local);
+        }
+
+        files
+    }
+
+    /// Returns the config files that are overridden by the environment
+    pub fn base_files(&self) -> Vec<PathBuf> {
+        match self {
+            ConfigLocation::Default => dir_files(&default_config_dir()),
+            ConfigLocation::File(path) => vec![path.clone()],
+            ConfigLocation::Dir(path) => dir_files(path),
+        }
+    }
 }
 
-fn load_settings() -> MultihookResult<Settings> {
-    let config_dir = dirs::config_dir()
-        .map(|c| c.join("multihook"))
-        .unwrap_or(PathBuf::from(".config"));
-    if !Path::new(&config_dir).exists() {
-        fs::create_dir(&config_dir)?;
-        write_toml_pretty(
-            &config_dir.clone().join("config.toml"),
-            &Settings::default(),
-        )?;
+/// Loads the settings from the config files and the environment
+pub fn load_settings(location: &ConfigLocation) -> MultihookResult<Settings> {
+    if let ConfigLocation::Default = location {
+        let config_dir = default_config_dir();
+        if !Path::new(&config_dir).exists() {
+            fs::create_dir(&config_dir)?;
+            write_toml_pretty(
+                &config_dir.clone().join("config.toml"),
+                &Settings::default(),
+            )?;
+        }
     }
 
-    let settings = Config::builder()
-        .add_source(
-            glob::glob(&format!("{}/*.toml", config_dir.to_string_lossy()))
-                .unwrap()
-                .map(|path| File::from(path.unwrap()))
-                .collect::<Vec<_>>(),
-        )
-        .add_source(config::Environment::with_prefix("MULTIHOOK"))
-        .add_source(File::from(PathBuf::from(".multihook.toml")).required(false))
-        .build()?;
+    let files = location.files();
+    let mut values = files
+        .iter()
+        .map(|path| read_config_file(path))
+        .collect::<MultihookResult<Vec<_>>>()?;
+    let profiles = collect_profiles(&values);
+    for value in &mut values {
+        resolve_profiles(value, &profiles).map_err(MultihookError::ProfileError)?;
+    }
 
-    let settings: Settings = settings.try_deserialize()?;
+    // the environment overrides the config directory but not the local config file
+    let local_values = values.split_off(location.base_files().len().min(files.len()));
+
+    let mut builder = Config::builder();
+    for value in &values {
+        builder = builder.add_source(File::from_str(&toml::to_string(value)?, FileFormat::Toml));
+    }
+    builder = builder.add_source(config::Environment::with_prefix("MULTIHOOK"));
+    for value in &local_values {
+        builder = builder.add_source(File::from_str(&toml::to_string(value)?, FileFormat::Toml));
+    }
+
+    let settings: Settings = builder.build()?.try_deserialize()?;
 
     Ok(settings)
 }
 
+/// Reads the config file in the format matching its extension
+/// and interpolates environment variables in its values
+pub fn read_config_file(path: &Path) -> MultihookResult<toml::Value> {
+    let content = fs::read_to_string(path)?;
+    let format = match path.extension().and_then(|e| e.to_str()) {
+        Some("yaml" | "yml") => Some(FileFormat::Yaml),
+        Some("json") => Some(FileFormat::Json),
+        _ => None,
+    };
+    let mut value = if let Some(format) = format {
+        Config::builder()
+            .add_source(File::from_str(&content, format))
+            .build()?
+            .try_deserialize()?
+    } else {
+        toml::from_str(&content)?
+    };
+    interpolate_value(&mut value).map_err(MultihookError::InterpolationError)?;
+
+    Ok(value)
+}
+
+fn default_config_dir() -> PathBuf {
+    dirs::config_dir()
+        .map(|c| c.join("multihook"))
+        .unwrap_or(PathBuf::from(".config"))
+}
+
+/// Returns the config files in the directory in the order they are merged in
+fn dir_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = CONFIG_EXTENSIONS
+        .iter()
+        .flat_map(|ext| {
+            glob::glob(&format!("{}/*.{}", dir.to_string_lossy(), ext))
+                .unwrap()
+                .filter_map(Result::ok)
+        })
+        .collect::<Vec<_>>();
+    files.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+
+    files
+}
+
 fn write_toml_pretty<T: Serialize>(path: &PathBuf, value: &T) -> MultihookResult<()> {
     fs::write(path, toml::to_string_pretty(value)?)?;
 
@@ -0,0 +1,139 @@
+//! Integration with the systemd service manager.
+//! Implements the socket activation and notification protocols
+//! without linking against libsystemd.
+
+use std::env;
+use std::time::Duration;
+
+static LISTEN_FDS_START: i32 = 3;
+
+/// The variables of the socket activation protocol. They are left in the environment
+/// since modifying it isn't safe once threads are running, but aren't passed to actions
+pub static LISTEN_VARS: &[&str] = &["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"];
+
+/// Takes over the file descriptors passed via socket activation.
+/// They are only used if `LISTEN_PID` matches this process
+#[cfg(unix)]
+pub fn take_listen_fds() -> Vec<std::os::unix::io::RawFd> {
+    use nix::fcntl::{fcntl, FcntlArg, FdFlag};
+
+    let pid_matches = env::var("LISTEN_PID")
+        .ok()
+        .and_then(|pid| pid.parse::<u32>().ok())
+        .map(|pid| pid == std::process::id())
+        .unwrap_or(false);
+    let count = env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|fds| fds.parse::<i32>().ok())
+        .unwrap_or(0);
+    if !pid_matches {
+        return Vec::new();
+    }
+
+    (LISTEN_FDS_START..LISTEN_FDS_START + count)
+        .inspect(|fd| {
+            // the descriptors must not leak into the processes spawned for actions
+            if let Err(e) = fcntl(*fd, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC)) {
+                log::warn!("Failed to set close-on-exec on fd {}: {}", fd, e);
+            }
+        })
+        .collect()
+}
+
+#[cfg(not(unix))]
+pub fn take_listen_fds() -> Vec<i32> {
+    Vec::new()
+}
+
+/// Sends a notification like `READY=1` to the service manager.
+/// Does nothing if the service wasn't started with a notification socket.
+pub fn notify(state: &str) {
+    #[cfg(unix)]
+    if let Some(socket) = env::var_os("NOTIFY_SOCKET") {
+        if let Err(e) = send_notification(&socket, state) {
+            log::warn!("Failed to notify service manager: {}", e);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = state;
+}
+
+#[cfg(unix)]
+fn send_notification(socket: &std::ffi::OsStr, state: &str) -> std::io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::net::UnixDatagram;
+
+    let datagram = UnixDatagram::unbound()?;
+    let path = socket.as_bytes();
+
+    #[cfg(target_os = "linux")]
+    if let Some(name) = path.strip_prefix(b"@") {
+        use std::os::linux::net::SocketAddrExt;
+        let address = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+        datagram.send_to_addr(state.as_bytes(), &address)?;
+        return Ok(());
+    }
+    datagram.send_to(state.as_bytes(), std::ffi::OsStr::from_bytes(path))?;
+
+    Ok(())
+}
+
+/// Returns the interval in which the watchdog needs to be pinged
+/// if it is enabled for this process
+pub fn watchdog_interval() -> Option<Duration> {
+    let pid_matches = env::var("WATCHDOG_PID")
+        .ok()
+        .and_then(|pid| pid.parse::<u32>().ok())
+        .map(|pid| pid == std::process::id())
+        .unwrap_or(true);
+    let usec = env::var("WATCHDOG_USEC").ok()?.parse::<u64>().ok()?;
+
+    if pid_matches && usec > 0 {
+        Some(Duration::from_micros(usec))
+    } else {
+        None
+    }
+}
+
+/// Pings the watchdog at half the configured interval
+pub fn spawn_watchdog() {
+    if let Some(interval) = watchdog_interval() {
+        log::info!("Sending watchdog pings every {:?}", interval / 2);
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval / 2);
+            loop {
+                interval.tick().await;
+                notify("WATCHDOG=1");
+            }
+        });
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixDatagram;
+
+    #[test]
+    fn it_sends_notifications_to_the_socket() {
+        let path = env::temp_dir().join(format!("multihook-notify-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let socket = UnixDatagram::bind(&path).unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+        env::set_var("NOTIFY_SOCKET", &path);
+
+        let mut buffer = [0u8; 64];
+        notify("READY=1");
+        let len = socket.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], b"READY=1");
+        notify("STOPPING=1");
+        let len = socket.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], b"STOPPING=1");
+
+        env::remove_var("NOTIFY_SOCKET");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
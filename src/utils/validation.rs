@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use crate::server::action::Action;
+use crate::server::endpoint::parse_method;
+use crate::server::route::RoutePattern;
+use crate::utils::profiles::{collect_profiles, merge, resolve_profiles};
+use crate::utils::settings::{load_settings, read_config_file, ConfigLocation, Hooks, Settings};
+
+/// An error in the config with the location it was found at
+#[derive(Debug)]
+pub struct ValidationError {
+    pub file: Option<PathBuf>,
+    pub key: Option<String>,
+    pub message: String,
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}: ", file.to_string_lossy())?;
+        }
+        if let Some(key) = &self.key {
+            write!(f, "{}: ", key)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// The parsed contents of the config files used to locate keys
+struct ConfigFiles(Vec<(PathBuf, toml::Table)>);
+
+impl ConfigFiles {
+    /// Returns the file with the highest precedence that defines the key.
+    /// Falls back to the closest parent key that is defined
+    fn locate(&self, key: &[&str]) -> Option<PathBuf> {
+        (1..=key.len()).rev().find_map(|len| {
+            self.0
+                .iter()
+                .rev()
+                .find(|(_, table)| defines(table, &key[..len]))
+                .map(|(path, _)| path.clone())
+        })
+    }
+
+    fn error(&self, key: &[&str], message: String) -> ValidationError {
+        ValidationError {
+            file: self.locate(key),
+            key: Some(key.join(".")),
+            message,
+        }
+    }
+}
+
+/// Loads the settings and validates them strictly.
+/// Unknown keys, duplicate or unreachable paths, invalid placeholders
+/// and missing script files are reported as errors.
+pub fn validate_config(location: &ConfigLocation) -> Result<Settings, Vec<ValidationError>> {
+    let mut errors = Vec::new();
+    let mut files = Vec::new();
+
+    let mut values = Vec::new();
+
+    for path in location.files() {
+        match read_config_file(&path) {
+            Ok(value) => values.push((path, value)),
+            Err(e) => errors.push(ValidationError {
+                file: Some(path.clone()),
+                key: None,
+                message: e.to_string(),
+            }),
+        }
+    }
+    let profiles = collect_profiles(values.iter().map(|(_, value)| value));
+    let mut merged = toml::Table::new();
+
+    for (path, mut value) in values {
+        if let Err(e) = resolve_profiles(&mut value, &profiles) {
+            errors.push(ValidationError {
+                file: Some(path),
+                key: None,
+                message: e,
+            });
+            continue;
+        }
+        if let toml::Value::Table(table) = value {
+            merge(&mut merged, &table);
+            files.push((path, table));
+        }
+    }
+    errors.append(&mut check_conflicts(&location.base_files(), &files));
+    let files = ConfigFiles(files);
+    // files can override parts of other files so only the merged config has to be complete
+    errors.append(&mut check_keys(merged, &files));
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    let settings = load_settings(location).map_err(|e| {
+        vec![ValidationError {
+            file: None,
+            key: None,
+            message: e.to_string(),
+        }]
+    })?;
+    let errors = check_settings(&settings, &files);
+
+    if errors.is_empty() {
+        Ok(settings)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Deserializes the merged config and reports invalid values and
+/// unknown keys in every file that contains them
+fn check_keys(merged: toml::Table, files: &ConfigFiles) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+    let mut unknown = Vec::new();
+    let mut collect = |path: serde_ignored::Path| unknown.push(path.to_string());
+    let deserializer = serde_ignored::Deserializer::new(toml::Value::Table(merged), &mut collect);
+
+    if let Err(e) = serde_path_to_error::deserialize::<_, Settings>(deserializer) {
+        let key = e.path().to_string();
+        let parts = key.split('.').collect::<Vec<_>>();
+        errors.push(ValidationError {
+            file: files.locate(&parts),
+            key: Some(key).filter(|key| key != "."),
+            message: e.inner().to_string().trim().to_owned(),
+        });
+    }
+    for key in unknown {
+        let parts = key.split('.').collect::<Vec<_>>();
+        for (path, _) in files.0.iter().filter(|(_, table)| defines(table, &parts)) {
+            errors.push(ValidationError {
+                file: Some(path.clone()),
+                key: Some(key.clone()),
+                message: String::from("unknown key"),
+            });
+        }
+    }
+
+    errors
+}
+
+/// Returns true if the table contains the dotted key
+fn defines(table: &toml::Table, key: &[&str]) -> bool {
+    let Some((first, rest)) = key.split_first() else {
+        return false;
+    };
+    let mut value = table.get(*first);
+    for part in rest {
+        value = value.and_then(|value| match value {
+            toml::Value::Array(values) => part.parse::<usize>().ok().and_then(|i| values.get(i)),
+            value => value.get(part),
+        });
+    }
+
+    value.is_some()
+}
+
+/// Reports endpoints that are defined in more than one of the given files
+fn check_conflicts(paths: &[PathBuf], files: &[(PathBuf, toml::Table)]) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+    let mut defined: HashMap<&str, &Path> = HashMap::new();
+
+    for (path, table) in files.iter().filter(|(path, _)| paths.contains(path)) {
+        let Some(endpoints) = table.get("endpoints").and_then(toml::Value::as_table) else {
+            continue;
+        };
+        for name in endpoints.keys() {
+            if let Some(other) = defined.insert(name, path) {
+                errors.push(ValidationError {
+                    file: Some(path.clone()),
+                    key: Some(format!("endpoints.{}", name)),
+                    message: format!(
+                        "endpoint '{}' is already defined in {}",
+                        name,
+                        other.to_string_lossy()
+                    ),
+                });
+            }
+        }
+    }
+
+    errors
+}
+
+fn check_settings(settings: &Settings, files: &ConfigFiles) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+    if let Some(hooks) = &settings.hooks {
+        // the variables are defined by the endpoints the global hooks are executed for
+        check_hooks(hooks, &["hooks"], None, files, &mut errors);
+    }
+    let mut endpoints = settings.endpoints.iter().collect::<Vec<_>>();
+    endpoints.sort_by_key(|(name, _)| *name);
+    let mut routes: HashMap<String, &str> = HashMap::new();
+    // client certificates are only requested if a ca to verify them is configured
+    let client_ca = settings
+        .server
+        .tls
+        .as_ref()
+        .is_some_and(|tls| tls.client_ca.is_some());
+
+    for (name, endpoint) in endpoints {
+        let key = ["endpoints", name.as_str()];
+        let path_key = ["endpoints", name, "path"];
+
+        if endpoint.path.starts_with('/') {
+            errors.push(files.error(
+                &path_key,
+                format!(
+                    "path '{}' can never match because paths are matched without the leading '/'",
+                    endpoint.path
+                ),
+            ));
+        }
+        if endpoint.methods.as_ref().is_some_and(Vec::is_empty) {
+            errors.push(files.error(
+                &[key[0], key[1], "methods"],
+                String::from("at least one method has to be allowed"),
+            ));
+        }
+        for method in endpoint.methods.iter().flatten() {
+            if let Err(e) = parse_method(method) {
+                errors.push(files.error(&[key[0], key[1], "methods"], e));
+            }
+        }
+        if endpoint.client_certificate.is_some() && !client_ca {
+            errors.push(files.error(
+                &[key[0], key[1], "client_certificate"],
+                String::from("requires `client_ca` to be configured in the tls settings"),
+            ));
+        }
+        let mut names = endpoint
+            .variables
+            .keys()
+            .map(|name| format!("vars.{}", name))
+            .collect::<Vec<_>>();
+        match RoutePattern::parse(&endpoint.path, &endpoint.params) {
+            Ok(pattern) => {
+                // routes that only differ in the names of their parameters match the same paths
+                if let Some(other) = routes.insert(pattern.shape(), name) {
+                    errors.push(files.error(
+                        &path_key,
+                        format!(
+                            "path '{}' matches the same requests as endpoint '{}'",
+                            endpoint.path, other
+                        ),
+                    ));
+                }
+                names.extend(
+                    pattern
+                        .param_names()
+                        .into_iter()
+                        .map(|name| format!("params.{}", name)),
+                )
+            }
+            Err(e) => errors.push(files.error(&path_key, e)),
+        }
+        check_action(
+            &endpoint.action,
+            &["endpoints", name, "action"],
+            Some(&names),
+            files,
+            &mut errors,
+        );
+        if let Some(hooks) = &endpoint.hooks {
+            check_hooks(
+                hooks,
+                &[key[0], key[1], "hooks"],
+                Some(&names),
+                files,
+                &mut errors,
+            );
+        }
+    }
+
+    errors
+}
+
+fn check_hooks(
+    hooks: &Hooks,
+    key: &[&str],
+    names: Option<&[String]>,
+    files: &ConfigFiles,
+    errors: &mut Vec<ValidationError>,
+) {
+    let actions = [
+        ("pre_action", &hooks.pre_action),
+        ("post_action", &hooks.post_action),
+        ("err_action", &hooks.err_action),
+    ];
+    for (name, action) in actions {
+        if let Some(action) = action {
+            let key = key.iter().copied().chain([name]).collect::<Vec<_>>();
+            check_action(action, &key, names, files, errors);
+        }
+    }
+}
+
+fn check_action(
+    action: &str,
+    key: &[&str],
+    names: Option<&[String]>,
+    files: &ConfigFiles,
+    errors: &mut Vec<ValidationError>,
+) {
+    for message in Action::validate(action, names) {
+        errors.push(files.error(key, message));
+    }
+    if let Some(program) = action.split_whitespace().next() {
+        // only commands that reference a script by its path can be checked
+        if program.contains('/') && !program.contains("{{") && !Path::new(program).exists() {
+            errors.push(files.error(key, format!("script file '{}' does not exist", program)));
+        }
+    }
+}